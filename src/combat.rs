@@ -0,0 +1,155 @@
+use rltk::{DiceType, RandomNumberGenerator};
+
+/// The armour class of a creature with no bonuses at all.
+pub const BASE_ARMOUR_CLASS: i32 = 10;
+
+/// Everything about an attacker that goes into one swing.
+pub struct Attack {
+    /// Added to the d20 roll.
+    pub to_hit: i32,
+    pub damage_dice: DiceType,
+    /// Added to the damage once, even on a critical.
+    pub damage_bonus: i32,
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum AttackResult {
+    /// A natural 1 misses whatever the bonuses.
+    Fumble,
+    Miss,
+    Hit { damage: i32 },
+    /// A natural 20 always hits and rolls the damage dice twice.
+    Critical { damage: i32 },
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct AttackOutcome {
+    pub natural_roll: i32,
+    pub total_roll: i32,
+    pub armour_class: i32,
+    pub result: AttackResult,
+}
+
+/// The usual d20 modifier: +0 at 10 or 11, one more for every two points above, one less for
+/// every two below.
+pub fn attribute_bonus(value: i32) -> i32 {
+    (value - 10).div_euclid(2)
+}
+
+pub fn armour_class(quickness: i32, defence_skill: i32, defence: i32) -> i32 {
+    BASE_ARMOUR_CLASS + attribute_bonus(quickness) + defence_skill + defence
+}
+
+pub fn roll_damage(rng: &mut RandomNumberGenerator, dice: DiceType) -> i32 {
+    rng.roll_dice(dice.n_dice, dice.die_type) + dice.bonus
+}
+
+/// Rolls one attack against `armour_class`. Every roll comes from `rng`, so a seeded
+/// generator always gives the same outcome.
+pub fn resolve_attack(rng: &mut RandomNumberGenerator, attack: &Attack, armour_class: i32) -> AttackOutcome {
+    let natural_roll = rng.roll_dice(1, 20);
+    let total_roll = natural_roll + attack.to_hit;
+
+    let result = if natural_roll == 1 {
+        AttackResult::Fumble
+    } else if natural_roll == 20 {
+        let dice = roll_damage(rng, attack.damage_dice) + roll_damage(rng, attack.damage_dice);
+        AttackResult::Critical { damage: i32::max(1, dice + attack.damage_bonus) }
+    } else if total_roll >= armour_class {
+        let dice = roll_damage(rng, attack.damage_dice);
+        AttackResult::Hit { damage: i32::max(1, dice + attack.damage_bonus) }
+    } else {
+        AttackResult::Miss
+    };
+
+    AttackOutcome { natural_roll, total_roll, armour_class, result }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const D6: DiceType = DiceType { n_dice: 1, die_type: 6, bonus: 0 };
+
+    fn attack(to_hit: i32, damage_bonus: i32) -> Attack {
+        Attack { to_hit, damage_dice: D6, damage_bonus }
+    }
+
+    /// Outcomes of one attack for each of a run of seeds.
+    fn outcomes(attack: &Attack, armour_class: i32) -> Vec<AttackOutcome> {
+        (0..2000).map(|seed| resolve_attack(&mut RandomNumberGenerator::seeded(seed), attack, armour_class)).collect()
+    }
+
+    #[test]
+    fn attribute_bonus_steps_every_two_points() {
+        assert_eq!(attribute_bonus(8), -1);
+        assert_eq!(attribute_bonus(9), -1);
+        assert_eq!(attribute_bonus(10), 0);
+        assert_eq!(attribute_bonus(11), 0);
+        assert_eq!(attribute_bonus(12), 1);
+    }
+
+    #[test]
+    fn armour_class_adds_quickness_skill_and_defence() {
+        assert_eq!(armour_class(10, 0, 0), BASE_ARMOUR_CLASS);
+        assert_eq!(armour_class(14, 1, 3), BASE_ARMOUR_CLASS + 2 + 1 + 3);
+    }
+
+    #[test]
+    fn a_natural_one_always_fumbles() {
+        let ones: Vec<AttackOutcome> = outcomes(&attack(100, 0), 0).into_iter().filter(|o| o.natural_roll == 1).collect();
+        assert!(!ones.is_empty());
+        assert!(ones.iter().all(|o| o.result == AttackResult::Fumble));
+    }
+
+    #[test]
+    fn a_natural_twenty_always_crits() {
+        let twenties: Vec<AttackOutcome> = outcomes(&attack(0, 0), 100).into_iter().filter(|o| o.natural_roll == 20).collect();
+        assert!(!twenties.is_empty());
+        for outcome in twenties {
+            match outcome.result {
+                // Two d6 rolled, so at least 2 and at most 12
+                AttackResult::Critical { damage } => assert!((2..=12).contains(&damage)),
+                other => panic!("a natural 20 gave {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn other_rolls_hit_only_when_they_reach_the_armour_class() {
+        for outcome in outcomes(&attack(2, 0), 14) {
+            if outcome.natural_roll == 1 || outcome.natural_roll == 20 {
+                continue;
+            }
+            assert_eq!(outcome.total_roll, outcome.natural_roll + 2);
+            match outcome.result {
+                AttackResult::Hit { damage } => {
+                    assert!(outcome.total_roll >= 14);
+                    assert!((1..=6).contains(&damage));
+                }
+                AttackResult::Miss => assert!(outcome.total_roll < 14),
+                other => panic!("rolled {} and got {:?}", outcome.natural_roll, other),
+            }
+        }
+    }
+
+    #[test]
+    fn damage_never_drops_below_one() {
+        for outcome in outcomes(&attack(0, -50), 0) {
+            match outcome.result {
+                AttackResult::Hit { damage } | AttackResult::Critical { damage } => assert_eq!(damage, 1),
+                AttackResult::Fumble | AttackResult::Miss => {}
+            }
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_outcome() {
+        let swing = attack(3, 1);
+        for seed in 0..100 {
+            let first = resolve_attack(&mut RandomNumberGenerator::seeded(seed), &swing, 13);
+            let second = resolve_attack(&mut RandomNumberGenerator::seeded(seed), &swing, 13);
+            assert_eq!(first, second);
+        }
+    }
+}
@@ -1,42 +1,47 @@
 use specs::prelude::*;
+use specs::saveload::{ConvertSaveload, Marker};
+use specs::error::NoError;
 use specs_derive::*;
+use serde::{Deserialize, Serialize};
 use rltk::{RGB};
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
 }
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Renderable {
     pub glyph: rltk::FontCharType,
     pub fg: RGB,
     pub bg: RGB,
+    /// Lower values are drawn on top when several entities share a tile.
+    pub render_order: i32,
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Player {}
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Viewshed {
     pub visible_tiles: Vec<rltk::Point>,
     pub range: i32,
     pub dirty: bool,
 }
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Monster {}
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Name {
     pub name: String
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct BlocksTile {}
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct CombatStats {
     pub max_hp: i32,
     pub current_hp: i32,
@@ -44,23 +49,231 @@ pub struct CombatStats {
     pub power: i32,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, ConvertSaveload)]
 pub struct WantsToMelee {
     pub target: Entity
 }
 
-#[derive(Component, Debug)]
+/// Pending hits, each with the entity that dealt it. Applied in the same turn it's created,
+/// so it is never around to be saved.
+#[derive(Component, Debug, Clone)]
 pub struct SufferDamage {
-    pub amount: Vec<i32>
+    pub amount: Vec<(i32, Entity)>
 }
 
 impl SufferDamage {
-    pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32) {
+    pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32, source: Entity) {
         if let Some(suffering) = store.get_mut(victim) {
-            suffering.amount.push(amount);
+            suffering.amount.push((amount, source));
         } else {
-            let dmg = SufferDamage { amount : vec![amount] };
+            let dmg = SufferDamage { amount : vec![(amount, source)] };
             store.insert(victim, dmg).expect("Unable to insert damage");
         }
     }
-}
\ No newline at end of file
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct ReceiveHealing {
+    pub amount: Vec<i32>
+}
+
+impl ReceiveHealing {
+    pub fn new_healing(store: &mut WriteStorage<ReceiveHealing>, patient: Entity, amount: i32) {
+        if let Some(healing) = store.get_mut(patient) {
+            healing.amount.push(amount);
+        } else {
+            let heal = ReceiveHealing { amount : vec![amount] };
+            store.insert(patient, heal).expect("Unable to insert healing");
+        }
+    }
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Item {}
+
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct InBackpack {
+    pub owner: Entity
+}
+
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct WantsToPickupItem {
+    pub collected_by: Entity,
+    pub item: Entity
+}
+
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct WantsToDropItem {
+    pub item: Entity
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Consumable {}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct ProvidesHealing {
+    pub amount: i32
+}
+
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct WantsToUseItem {
+    pub item: Entity,
+    pub target: Option<rltk::Point>
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Ranged {
+    pub range: i32
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct InflictsDamage {
+    pub damage: i32
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct AreaOfEffect {
+    pub radius: i32
+}
+
+/// On an item, how long it confuses its target for; on a creature, how many turns it has left.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Confusion {
+    pub turns: i32
+}
+
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    Melee,
+    Shield,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Equippable {
+    pub slot: EquipmentSlot
+}
+
+/// An item being worn or wielded. Equipped items are out of the backpack until removed.
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct Equipped {
+    pub owner: Entity,
+    pub slot: EquipmentSlot
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct MeleePowerBonus {
+    pub power: i32
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct DefenceBonus {
+    pub defence: i32
+}
+
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct WantsToRemoveItem {
+    pub item: Entity
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Experience {
+    pub level: i32,
+    pub xp: i32,
+    /// Levels gained but not yet spent on the level-up screen.
+    pub unspent_levels: i32
+}
+
+impl Experience {
+    /// Total XP needed to go from `level` to the next one.
+    pub fn xp_to_next_level(level: i32) -> i32 {
+        level * 100
+    }
+}
+
+/// XP awarded to whoever lands the killing blow.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct ExperienceValue {
+    pub xp: i32
+}
+
+/// Raw ability scores. 10 is average; see `combat::attribute_bonus` for what each is worth.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Attributes {
+    /// Melee accuracy and damage.
+    pub might: i32,
+    /// Extra max HP with each level.
+    pub fitness: i32,
+    /// Harder to hit.
+    pub quickness: i32,
+    /// Stronger damage scrolls.
+    pub intelligence: i32
+}
+
+impl Default for Attributes {
+    fn default() -> Self {
+        Attributes { might: 10, fitness: 10, quickness: 10, intelligence: 10 }
+    }
+}
+
+/// Trained bonuses, added straight onto the matching rolls.
+#[derive(Component, Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Skills {
+    pub melee: i32,
+    pub defence: i32,
+    pub magic: i32
+}
+
+/// On a weapon, what it hits for; on a creature, its unarmed or natural attack.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct DamageDice {
+    pub dice: rltk::DiceType
+}
+
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum HungerState {
+    WellFed,
+    Normal,
+    Hungry,
+    Starving
+}
+
+impl HungerState {
+    /// Turns spent in this state before sliding into the next. Starving lasts until fed.
+    pub fn duration(self) -> i32 {
+        match self {
+            HungerState::WellFed => 150,
+            HungerState::Normal => 300,
+            HungerState::Hungry => 150,
+            HungerState::Starving => 0,
+        }
+    }
+}
+
+/// Counts down the turns left in the current `state`.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct HungerClock {
+    pub state: HungerState,
+    pub duration: i32
+}
+
+impl HungerClock {
+    pub fn well_fed() -> Self {
+        HungerClock { state: HungerState::WellFed, duration: HungerState::WellFed.duration() }
+    }
+}
+
+/// Eating this resets the eater's `HungerClock` to well fed.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct ProvidesFood {}
+
+/// Marks the entities that belong in a save file.
+pub struct SerializeMe;
+
+/// Carries the resources that aren't components through a save, attached to a temporary entity.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct SerializationHelper {
+    pub map: super::map::Map,
+    pub run_stats: super::RunStats,
+    pub log: super::GameLog,
+    pub rng: rltk::RandomNumberGenerator,
+}
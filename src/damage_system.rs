@@ -1,21 +1,79 @@
+use rltk::RGB;
 use specs::prelude::*;
-use super::{CombatStats, SufferDamage};
+use super::combat::attribute_bonus;
+use super::{Attributes, CombatStats, Experience, ExperienceValue, GameLog, LogCategory, ReceiveHealing, RunStats, SufferDamage};
 
+/// Max HP and power gained with every level, before fitness and the bonus picked on the
+/// level-up screen.
+const LEVEL_HP_GAIN: i32 = 5;
+const LEVEL_POWER_GAIN: i32 = 1;
+
+/// Applies every pending HP change, damage and healing alike, capping healing at `max_hp`.
+/// Whoever lands a killing blow earns the victim's XP.
 pub struct DamageSystem{}
 
 impl<'a> System<'a> for DamageSystem {
+  #[allow(clippy::type_complexity)]
   type SystemData = (
+    Entities<'a>,
     WriteStorage<'a, CombatStats>,
-    WriteStorage<'a, SufferDamage>
+    WriteStorage<'a, SufferDamage>,
+    WriteStorage<'a, ReceiveHealing>,
+    ReadStorage<'a, ExperienceValue>,
+    WriteStorage<'a, Experience>,
+    ReadStorage<'a, Attributes>,
+    ReadExpect<'a, Entity>,
+    ReadExpect<'a, RunStats>,
+    WriteExpect<'a, GameLog>
   );
 
   fn run(&mut self, data: Self::SystemData) {
-    let (mut combat_stats, mut damage) = data;
+    let (entities, mut combat_stats, mut damage, mut healing, xp_values, mut experience, attributes, player_entity, run_stats,
+      mut log) = data;
+
+    let mut xp_awards: Vec<(Entity, i32)> = Vec::new();
+    for (victim, stats, damage) in (&entities, &mut combat_stats, &damage).join() {
+      for (amount, source) in damage.amount.iter() {
+        let was_alive = stats.current_hp > 0;
+        stats.current_hp -= amount;
+        if was_alive && stats.current_hp < 1 {
+          if let Some(value) = xp_values.get(victim) {
+            xp_awards.push((*source, value.xp));
+          }
+        }
+      }
+    }
+
+    for (killer, xp) in xp_awards {
+      let (exp, stats) = match (experience.get_mut(killer), combat_stats.get_mut(killer)) {
+        (Some(exp), Some(stats)) if stats.current_hp > 0 => (exp, stats),
+        _ => continue,
+      };
+      exp.xp += xp;
+      let fitness = attributes.get(killer).map(|a| attribute_bonus(a.fitness)).unwrap_or(0);
+      while exp.xp >= Experience::xp_to_next_level(exp.level) {
+        exp.xp -= Experience::xp_to_next_level(exp.level);
+        exp.level += 1;
+        exp.unspent_levels += 1;
+        stats.max_hp += i32::max(1, LEVEL_HP_GAIN + fitness);
+        stats.power += LEVEL_POWER_GAIN;
+        stats.current_hp = stats.max_hp;
+        if killer == *player_entity {
+          log.push(run_stats.turns, LogCategory::System, RGB::named(rltk::GOLD),
+            format!("Welcome to level {}! You feel stronger.", exp.level));
+        }
+      }
+    }
 
-    for (stats, damage) in (&mut combat_stats, &damage).join() {
-      stats.current_hp -= damage.amount.iter().sum::<i32>();
+    // Healing can't bring back anything the damage above has just killed
+    for (stats, healing) in (&mut combat_stats, &healing).join() {
+      if stats.current_hp < 1 {
+        continue;
+      }
+      stats.current_hp = i32::min(stats.max_hp, stats.current_hp + healing.amount.iter().sum::<i32>());
     }
 
     damage.clear();
+    healing.clear();
   }
-}
\ No newline at end of file
+}
@@ -0,0 +1,113 @@
+use rltk::RGB;
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum LogCategory {
+    System,
+    Combat,
+    Death,
+    Item,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub turn: i32,
+    pub colour: RGB,
+    pub category: LogCategory,
+    pub text: String,
+}
+
+/// Every message posted during the run, oldest first. `scroll` is how many entries the
+/// panel is currently scrolled back from the newest one.
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct GameLog {
+    entries: Vec<LogEntry>,
+    pub scroll: usize,
+}
+
+impl GameLog {
+    pub fn push<S: ToString>(&mut self, turn: i32, category: LogCategory, colour: RGB, text: S) {
+        self.entries.push(LogEntry { turn, colour, category, text: text.to_string() });
+        self.scroll = 0;
+    }
+
+    pub fn entries(&self) -> &[LogEntry] {
+        &self.entries
+    }
+
+    pub fn in_category(&self, category: LogCategory) -> impl Iterator<Item = &LogEntry> {
+        self.entries.iter().filter(move |e| e.category == category)
+    }
+
+    /// Up to `count` entries ending at the current scroll position, newest first.
+    pub fn visible(&self, count: usize) -> impl Iterator<Item = &LogEntry> {
+        self.entries.iter().rev().skip(self.scroll).take(count)
+    }
+
+    pub fn scroll_back(&mut self, lines: usize) {
+        self.scroll = usize::min(self.scroll + lines, self.entries.len().saturating_sub(1));
+    }
+
+    pub fn scroll_forward(&mut self, lines: usize) {
+        self.scroll = self.scroll.saturating_sub(lines);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_of(categories: &[LogCategory]) -> GameLog {
+        let mut log = GameLog::default();
+        for (turn, category) in categories.iter().enumerate() {
+            log.push(turn as i32, *category, RGB::named(rltk::WHITE), format!("entry {}", turn));
+        }
+        log
+    }
+
+    #[test]
+    fn in_category_keeps_only_matching_entries_in_order() {
+        let log = log_of(&[LogCategory::Combat, LogCategory::Item, LogCategory::Combat, LogCategory::System]);
+
+        let combat: Vec<&str> = log.in_category(LogCategory::Combat).map(|e| e.text.as_str()).collect();
+        assert_eq!(combat, vec!["entry 0", "entry 2"]);
+        assert_eq!(log.in_category(LogCategory::Death).count(), 0);
+    }
+
+    #[test]
+    fn visible_is_newest_first_from_the_scroll_position() {
+        let mut log = log_of(&[LogCategory::System; 5]);
+
+        let turns: Vec<i32> = log.visible(3).map(|e| e.turn).collect();
+        assert_eq!(turns, vec![4, 3, 2]);
+
+        log.scroll_back(2);
+        let turns: Vec<i32> = log.visible(3).map(|e| e.turn).collect();
+        assert_eq!(turns, vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn scrolling_is_clamped_at_both_ends() {
+        let mut log = log_of(&[LogCategory::System; 5]);
+
+        log.scroll_back(100);
+        assert_eq!(log.scroll, 4);
+        assert_eq!(log.visible(3).count(), 1);
+
+        log.scroll_forward(100);
+        assert_eq!(log.scroll, 0);
+
+        // A new message always snaps the panel back to the newest entry
+        log.scroll_back(2);
+        log.push(5, LogCategory::Item, RGB::named(rltk::WHITE), "new");
+        assert_eq!(log.scroll, 0);
+    }
+
+    #[test]
+    fn scrolling_an_empty_log_stays_at_zero() {
+        let mut log = GameLog::default();
+        log.scroll_back(3);
+        assert_eq!(log.scroll, 0);
+        assert_eq!(log.visible(4).count(), 0);
+    }
+}
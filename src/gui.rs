@@ -0,0 +1,484 @@
+use rltk::{Point, Rltk, VirtualKeyCode, RGB};
+use specs::prelude::*;
+use super::map_builders::MapBuilderKind;
+use super::{CombatStats, Confusion, Equipped, Experience, GameLog, HungerClock, HungerState, InBackpack, Map, Name, Player,
+    RunStats, Settings, State, Viewshed, MAPHEIGHT};
+
+const LOG_LINES: usize = 4;
+
+fn hp_colour(stats: &CombatStats) -> RGB {
+    let fraction = stats.current_hp as f32 / stats.max_hp as f32;
+    if fraction > 0.5 {
+        RGB::named(rltk::GREEN)
+    } else if fraction > 0.25 {
+        RGB::named(rltk::YELLOW)
+    } else {
+        RGB::named(rltk::RED)
+    }
+}
+
+pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
+    let panel_y = MAPHEIGHT as i32;
+    ctx.draw_box(0, panel_y, 79, 6, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+
+    let map = ecs.fetch::<Map>();
+    let run_stats = ecs.fetch::<RunStats>();
+    ctx.print_color(2, panel_y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), format!(" Depth: {} ", map.depth));
+    ctx.print_color(15, panel_y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), format!(" Turn: {} ", run_stats.turns));
+
+    let experience = ecs.read_storage::<Experience>();
+    let players = ecs.read_storage::<Player>();
+    for (_player, exp) in (&players, &experience).join() {
+        ctx.print_color(30, panel_y, RGB::named(rltk::GOLD), RGB::named(rltk::BLACK),
+            format!(" Level {} ({}/{} XP) ", exp.level, exp.xp, Experience::xp_to_next_level(exp.level)));
+    }
+
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    for (_player, stats) in (&players, &combat_stats).join() {
+        let colour = hp_colour(stats);
+        let health = format!(" HP: {} / {} ", stats.current_hp, stats.max_hp);
+        ctx.print_color(12, panel_y + 1, colour, RGB::named(rltk::BLACK), &health);
+        ctx.draw_bar_horizontal(28, panel_y + 1, 51, stats.current_hp, stats.max_hp, colour, RGB::named(rltk::BLACK));
+    }
+
+    let hunger_clocks = ecs.read_storage::<HungerClock>();
+    for (_player, clock) in (&players, &hunger_clocks).join() {
+        let (text, colour) = match clock.state {
+            HungerState::WellFed => ("Well Fed", RGB::named(rltk::GREEN)),
+            HungerState::Normal => ("Normal", RGB::named(rltk::GREY)),
+            HungerState::Hungry => ("Hungry", RGB::named(rltk::ORANGE)),
+            HungerState::Starving => ("Starving", RGB::named(rltk::RED)),
+        };
+        ctx.print_color(2, panel_y + 1, colour, RGB::named(rltk::BLACK), text);
+    }
+
+    let log = ecs.fetch::<GameLog>();
+    if log.scroll > 0 {
+        ctx.print_color(60, panel_y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), format!(" scrollback -{} ", log.scroll));
+    }
+
+    for (y, entry) in (panel_y + 2..).zip(log.visible(LOG_LINES)) {
+        ctx.print_color(2, y, entry.colour, RGB::named(rltk::BLACK), format!("[{}] {}", entry.turn, entry.text));
+    }
+
+    if ecs.fetch::<Settings>().show_tooltips {
+        draw_tooltips(ecs, ctx);
+    }
+}
+
+fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
+    let map = ecs.fetch::<Map>();
+    let names = ecs.read_storage::<Name>();
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let confused = ecs.read_storage::<Confusion>();
+
+    let (mouse_x, mouse_y) = ctx.mouse_pos();
+    if mouse_x < 0 || mouse_x >= map.width || mouse_y < 0 || mouse_y >= map.height {
+        return;
+    }
+
+    // Only describe what the player can currently see, so tooltips can't scout for them
+    let idx = map.xy_idx(mouse_x, mouse_y);
+    if !map.visible_tiles[idx] {
+        return;
+    }
+
+    let mut tooltip: Vec<String> = Vec::new();
+    for entity in map.tile_content[idx].iter() {
+        if let Some(name) = names.get(*entity) {
+            let mut line = name.name.clone();
+            if let Some(stats) = combat_stats.get(*entity) {
+                line.push_str(&format!(" ({}/{} hp)", stats.current_hp, stats.max_hp));
+                if let Some(confusion) = confused.get(*entity) {
+                    line.push_str(&format!(" [confused {}]", confusion.turns));
+                }
+            }
+            tooltip.push(line);
+        }
+    }
+
+    if tooltip.is_empty() {
+        return;
+    }
+
+    let text_width = tooltip.iter().map(|s| s.len()).max().unwrap_or(0);
+    let box_width = text_width as i32 + 2;
+    let fg = RGB::named(rltk::WHITE);
+    let bg = RGB::named(rltk::GREY);
+
+    // Flip the tooltip to the left of the cursor on the right half of the screen
+    let box_x = if mouse_x > 40 {
+        ctx.print_color(mouse_x - 2, mouse_y, fg, bg, "->");
+        mouse_x - 2 - box_width
+    } else {
+        ctx.print_color(mouse_x + 1, mouse_y, fg, bg, "<-");
+        mouse_x + 3
+    };
+
+    for (y, line) in (mouse_y..).zip(tooltip.iter()) {
+        ctx.print_color(box_x, y, fg, bg, format!(" {:<width$} ", line, width = text_width));
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum ItemMenuResult {
+    Cancel,
+    NoResponse,
+    Selected,
+}
+
+/// Lists everything the player is carrying under `title`, and returns the item picked by letter.
+pub fn show_inventory(gs: &mut State, ctx: &mut Rltk, title: &str) -> (ItemMenuResult, Option<Entity>) {
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let backpack = gs.ecs.read_storage::<InBackpack>();
+    let entities = gs.ecs.entities();
+
+    let items: Vec<(Entity, &Name)> = (&entities, &backpack, &names)
+        .join()
+        .filter(|(_entity, pack, _name)| pack.owner == *player_entity)
+        .map(|(entity, _pack, name)| (entity, name))
+        .collect();
+
+    item_menu(ctx, title, &items, "You are carrying nothing.")
+}
+
+/// Lists what the player is wearing or wielding.
+pub fn show_equipped(gs: &mut State, ctx: &mut Rltk, title: &str) -> (ItemMenuResult, Option<Entity>) {
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let equipped = gs.ecs.read_storage::<Equipped>();
+    let entities = gs.ecs.entities();
+
+    let items: Vec<(Entity, &Name)> = (&entities, &equipped, &names)
+        .join()
+        .filter(|(_entity, worn, _name)| worn.owner == *player_entity)
+        .map(|(entity, _worn, name)| (entity, name))
+        .collect();
+
+    item_menu(ctx, title, &items, "You have nothing equipped.")
+}
+
+fn item_menu(ctx: &mut Rltk, title: &str, items: &[(Entity, &Name)], empty_text: &str) -> (ItemMenuResult, Option<Entity>) {
+    let count = items.len() as i32;
+
+    let y = 25 - (count / 2);
+    ctx.draw_box(15, y - 2, 31, count + 3, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.print_color(18, y - 2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), title);
+    ctx.print_color(18, y + count + 1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "ESCAPE to cancel");
+
+    if items.is_empty() {
+        ctx.print_color(17, y, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), empty_text);
+    }
+
+    for (j, (_entity, name)) in items.iter().enumerate() {
+        let y = y + j as i32;
+        ctx.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
+        ctx.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97 + j as rltk::FontCharType);
+        ctx.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
+
+        ctx.print(21, y, &name.name);
+    }
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(VirtualKeyCode::Escape) => (ItemMenuResult::Cancel, None),
+        Some(key) => {
+            let selection = rltk::letter_to_option(key);
+            if selection > -1 && selection < count {
+                return (ItemMenuResult::Selected, Some(items[selection as usize].0));
+            }
+            (ItemMenuResult::NoResponse, None)
+        }
+    }
+}
+
+/// Highlights the tiles a click would walk the player along.
+pub fn draw_travel_path(ecs: &World, ctx: &mut Rltk, path: &[usize]) {
+    let map = ecs.fetch::<Map>();
+    for idx in path.iter() {
+        ctx.set_bg(*idx as i32 % map.width, *idx as i32 / map.width, RGB::named(rltk::DARK_SLATE));
+    }
+}
+
+/// Highlights the tiles the player can see within `range` and lets them pick one, either by
+/// clicking it or by moving `cursor` with the movement keys and pressing ENTER.
+pub fn ranged_target(gs: &mut State, ctx: &mut Rltk, range: i32, cursor: &mut Point) -> (ItemMenuResult, Option<Point>) {
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let player_pos = gs.ecs.fetch::<Point>();
+    let viewsheds = gs.ecs.read_storage::<Viewshed>();
+
+    ctx.print_color(5, 0, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Select Target (ENTER or click, ESCAPE to cancel):");
+
+    let mut available_cells = Vec::new();
+    if let Some(visible) = viewsheds.get(*player_entity) {
+        for tile in visible.visible_tiles.iter() {
+            let distance = rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, *tile);
+            if distance <= range as f32 {
+                ctx.set_bg(tile.x, tile.y, RGB::named(rltk::BLUE));
+                available_cells.push(*tile);
+            }
+        }
+    } else {
+        return (ItemMenuResult::Cancel, None);
+    }
+
+    let (mouse_x, mouse_y) = ctx.mouse_pos();
+    let mouse = Point::new(mouse_x, mouse_y);
+    let mouse_valid = available_cells.contains(&mouse);
+    let cursor_valid = available_cells.contains(cursor);
+
+    ctx.set_bg(mouse.x, mouse.y, if mouse_valid { RGB::named(rltk::CYAN) } else { RGB::named(rltk::RED) });
+    ctx.set_bg(cursor.x, cursor.y, if cursor_valid { RGB::named(rltk::CYAN) } else { RGB::named(rltk::RED) });
+
+    if ctx.left_click && mouse_valid {
+        return (ItemMenuResult::Selected, Some(mouse));
+    }
+
+    match ctx.key {
+        None => {}
+        Some(VirtualKeyCode::Escape) => return (ItemMenuResult::Cancel, None),
+        Some(VirtualKeyCode::Return) if cursor_valid => return (ItemMenuResult::Selected, Some(*cursor)),
+        Some(key) => {
+            let (dx, dy) = match key {
+                VirtualKeyCode::Left | VirtualKeyCode::Numpad4 | VirtualKeyCode::H => (-1, 0),
+                VirtualKeyCode::Right | VirtualKeyCode::Numpad6 | VirtualKeyCode::L => (1, 0),
+                VirtualKeyCode::Up | VirtualKeyCode::Numpad8 | VirtualKeyCode::K => (0, -1),
+                VirtualKeyCode::Down | VirtualKeyCode::Numpad2 | VirtualKeyCode::J => (0, 1),
+                VirtualKeyCode::Numpad7 | VirtualKeyCode::Y => (-1, -1),
+                VirtualKeyCode::Numpad9 | VirtualKeyCode::U => (1, -1),
+                VirtualKeyCode::Numpad1 | VirtualKeyCode::B => (-1, 1),
+                VirtualKeyCode::Numpad3 | VirtualKeyCode::N => (1, 1),
+                _ => (0, 0),
+            };
+            let candidate = Point::new(cursor.x + dx, cursor.y + dy);
+            if available_cells.contains(&candidate) {
+                *cursor = candidate;
+            }
+        }
+    }
+
+    (ItemMenuResult::NoResponse, None)
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum MainMenuSelection {
+    NewGame,
+    Continue,
+    Settings,
+    Quit,
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum MainMenuResult {
+    NoSelection { selected: MainMenuSelection },
+    Selected { selected: MainMenuSelection },
+}
+
+/// Draws a centred list of `(label, enabled)` entries starting at row `top`. The highlight moves
+/// with the arrow keys, and clicking an entry activates it directly. Disabled entries are greyed
+/// out and skipped. Returns the highlighted entry and whether it was activated.
+fn menu_list(ctx: &mut Rltk, top: i32, entries: &[(String, bool)], selected: usize) -> (usize, bool) {
+    let mut selected = selected;
+    if !entries[selected].1 {
+        selected = entries.iter().position(|(_, enabled)| *enabled).unwrap_or(0);
+    }
+
+    let (mouse_x, mouse_y) = ctx.mouse_pos();
+    let mut clicked = false;
+    for (i, (label, enabled)) in entries.iter().enumerate() {
+        let y = top + i as i32;
+        let left = 40 - label.len() as i32 / 2;
+        if ctx.left_click && *enabled && mouse_y == y && mouse_x >= left && mouse_x < left + label.len() as i32 {
+            selected = i;
+            clicked = true;
+        }
+    }
+
+    for (i, (label, enabled)) in entries.iter().enumerate() {
+        let fg = if !enabled {
+            RGB::named(rltk::GREY)
+        } else if i == selected {
+            RGB::named(rltk::MAGENTA)
+        } else {
+            RGB::named(rltk::WHITE)
+        };
+        ctx.print_color_centered(top + i as i32, fg, RGB::named(rltk::BLACK), label);
+    }
+
+    if clicked {
+        return (selected, true);
+    }
+
+    let step = |from: usize, forward: bool| {
+        let mut i = from;
+        loop {
+            i = if forward { (i + 1) % entries.len() } else { (i + entries.len() - 1) % entries.len() };
+            if entries[i].1 || i == from {
+                return i;
+            }
+        }
+    };
+
+    match ctx.key {
+        Some(VirtualKeyCode::Up) | Some(VirtualKeyCode::K) => (step(selected, false), false),
+        Some(VirtualKeyCode::Down) | Some(VirtualKeyCode::J) => (step(selected, true), false),
+        Some(VirtualKeyCode::Return) => (selected, true),
+        _ => (selected, false),
+    }
+}
+
+pub fn main_menu(ctx: &mut Rltk, selection: MainMenuSelection, message: Option<&str>, save_exists: bool) -> MainMenuResult {
+    let options = [
+        (MainMenuSelection::NewGame, "New Game", true),
+        (MainMenuSelection::Continue, "Continue", save_exists),
+        (MainMenuSelection::Settings, "Settings", true),
+        (MainMenuSelection::Quit, "Quit", true),
+    ];
+
+    ctx.draw_box_double(24, 12, 31, 14, RGB::named(rltk::WHEAT), RGB::named(rltk::BLACK));
+    ctx.print_color_centered(14, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Rustly");
+    ctx.print_color_centered(16, RGB::named(rltk::CYAN), RGB::named(rltk::BLACK), "Use arrow keys or the mouse");
+
+    if let Some(message) = message {
+        ctx.print_color_centered(28, RGB::named(rltk::RED), RGB::named(rltk::BLACK), message);
+    }
+
+    let entries: Vec<(String, bool)> = options.iter().map(|(_, label, enabled)| (label.to_string(), *enabled)).collect();
+    let current = options.iter().position(|(option, _, _)| *option == selection).unwrap_or(0);
+    let (highlighted, activated) = menu_list(ctx, 19, &entries, current);
+    let selected = options[highlighted].0;
+
+    if activated {
+        MainMenuResult::Selected { selected }
+    } else if ctx.key == Some(VirtualKeyCode::Escape) {
+        MainMenuResult::NoSelection { selected: MainMenuSelection::Quit }
+    } else {
+        MainMenuResult::NoSelection { selected }
+    }
+}
+
+/// Cycles random -> each builder in turn -> random.
+fn next_map_builder(current: Option<MapBuilderKind>) -> Option<MapBuilderKind> {
+    match current {
+        None => Some(MapBuilderKind::ALL[0]),
+        Some(kind) => {
+            let idx = MapBuilderKind::ALL.iter().position(|k| *k == kind).unwrap_or(0);
+            MapBuilderKind::ALL.get(idx + 1).copied()
+        }
+    }
+}
+
+fn edit_seed(seed: Option<u64>, key: VirtualKeyCode) -> Option<u64> {
+    let digit = match key {
+        VirtualKeyCode::Key0 | VirtualKeyCode::Numpad0 => Some(0),
+        VirtualKeyCode::Key1 | VirtualKeyCode::Numpad1 => Some(1),
+        VirtualKeyCode::Key2 | VirtualKeyCode::Numpad2 => Some(2),
+        VirtualKeyCode::Key3 | VirtualKeyCode::Numpad3 => Some(3),
+        VirtualKeyCode::Key4 | VirtualKeyCode::Numpad4 => Some(4),
+        VirtualKeyCode::Key5 | VirtualKeyCode::Numpad5 => Some(5),
+        VirtualKeyCode::Key6 | VirtualKeyCode::Numpad6 => Some(6),
+        VirtualKeyCode::Key7 | VirtualKeyCode::Numpad7 => Some(7),
+        VirtualKeyCode::Key8 | VirtualKeyCode::Numpad8 => Some(8),
+        VirtualKeyCode::Key9 | VirtualKeyCode::Numpad9 => Some(9),
+        _ => None,
+    };
+
+    match (key, digit) {
+        (VirtualKeyCode::Back, _) => seed.map(|s| s / 10).filter(|s| *s > 0),
+        (_, Some(d)) => Some(seed.unwrap_or(0).saturating_mul(10).saturating_add(d)),
+        _ => seed,
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum SettingsMenuResult {
+    NoResponse { selected: usize },
+    Back,
+}
+
+/// Lists each setting with its current value; activating one cycles it in place.
+pub fn settings_menu(gs: &mut State, ctx: &mut Rltk, selection: usize) -> SettingsMenuResult {
+    let mut settings = gs.ecs.write_resource::<Settings>();
+
+    ctx.draw_box_double(20, 12, 39, 14, RGB::named(rltk::WHEAT), RGB::named(rltk::BLACK));
+    ctx.print_color_centered(14, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Settings");
+
+    let on_off = |value: bool| if value { "On" } else { "Off" };
+    let seed = match settings.seed {
+        Some(seed) => seed.to_string(),
+        None => "random".to_string(),
+    };
+    let entries = vec![
+        (format!("Mouse tooltips: {}", on_off(settings.show_tooltips)), true),
+        (format!("Seed: {}", seed), true),
+        (format!("Map: {}", settings.map_builder.map(|kind| kind.name()).unwrap_or("random")), true),
+        (format!("Replay map generation: {}", on_off(settings.show_mapgen)), true),
+        ("Back".to_string(), true),
+    ];
+    let back = entries.len() - 1;
+
+    let (highlighted, activated) = menu_list(ctx, 18, &entries, usize::min(selection, back));
+
+    if highlighted == 1 {
+        ctx.print_color_centered(23, RGB::named(rltk::CYAN), RGB::named(rltk::BLACK), "Type digits to set, BACKSPACE to clear");
+        if let Some(key) = ctx.key {
+            settings.seed = edit_seed(settings.seed, key);
+        }
+    }
+
+    if ctx.key == Some(VirtualKeyCode::Escape) || (activated && highlighted == back) {
+        return SettingsMenuResult::Back;
+    }
+
+    if activated && highlighted == 0 {
+        settings.show_tooltips = !settings.show_tooltips;
+    }
+
+    if activated && highlighted == 2 {
+        settings.map_builder = next_map_builder(settings.map_builder);
+    }
+
+    if activated && highlighted == 3 {
+        settings.show_mapgen = !settings.show_mapgen;
+    }
+
+    SettingsMenuResult::NoResponse { selected: highlighted }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum LevelUpBonus {
+    Toughness,
+    Strength,
+    Guard,
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum LevelUpResult {
+    NoResponse { selected: usize },
+    Chosen { bonus: LevelUpBonus },
+}
+
+/// Asks the player which extra bonus to take for their new level. There is no way to back
+/// out; the choice has to be made before play carries on.
+pub fn level_up(ecs: &World, ctx: &mut Rltk, selection: usize) -> LevelUpResult {
+    let player_entity = ecs.fetch::<Entity>();
+    let level = ecs.read_storage::<Experience>().get(*player_entity).map(|exp| exp.level).unwrap_or(1);
+
+    ctx.draw_box_double(20, 15, 39, 10, RGB::named(rltk::GOLD), RGB::named(rltk::BLACK));
+    ctx.print_color_centered(17, RGB::named(rltk::GOLD), RGB::named(rltk::BLACK), format!("You have reached level {}!", level));
+    ctx.print_color_centered(18, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Choose a bonus:");
+
+    let bonuses = [
+        (LevelUpBonus::Toughness, "Toughness: +5 max HP"),
+        (LevelUpBonus::Strength, "Strength: +1 power"),
+        (LevelUpBonus::Guard, "Guard: +1 defence"),
+    ];
+    let entries: Vec<(String, bool)> = bonuses.iter().map(|(_, label)| (label.to_string(), true)).collect();
+
+    let (highlighted, activated) = menu_list(ctx, 20, &entries, usize::min(selection, bonuses.len() - 1));
+    if activated {
+        return LevelUpResult::Chosen { bonus: bonuses[highlighted].0 };
+    }
+    LevelUpResult::NoResponse { selected: highlighted }
+}
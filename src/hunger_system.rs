@@ -0,0 +1,52 @@
+use rltk::RGB;
+use specs::prelude::*;
+use super::{GameLog, HungerClock, HungerState, LogCategory, RunStats, SufferDamage};
+
+/// HP lost each turn spent starving.
+const STARVATION_DAMAGE: i32 = 1;
+
+/// Ticks every hunger clock down once per player turn, moving on to the next state when the
+/// current one runs out. Starving hurts every turn until something is eaten.
+pub struct HungerSystem {}
+
+impl<'a> System<'a> for HungerSystem {
+    type SystemData = ( Entities<'a>,
+                        WriteStorage<'a, HungerClock>,
+                        ReadExpect<'a, Entity>,
+                        WriteExpect<'a, RunStats>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, SufferDamage>
+                      );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, mut hunger_clocks, player_entity, mut run_stats, mut log, mut inflict_damage) = data;
+
+        for (entity, clock) in (&entities, &mut hunger_clocks).join() {
+            let is_player = entity == *player_entity;
+
+            if clock.state == HungerState::Starving {
+                SufferDamage::new_damage(&mut inflict_damage, entity, STARVATION_DAMAGE, entity);
+                if is_player {
+                    run_stats.last_attacker = Some("starvation".to_string());
+                }
+                continue;
+            }
+
+            clock.duration -= 1;
+            if clock.duration > 0 {
+                continue;
+            }
+
+            let (next, message, colour) = match clock.state {
+                HungerState::WellFed => (HungerState::Normal, "You are no longer well fed.", RGB::named(rltk::WHITE)),
+                HungerState::Normal => (HungerState::Hungry, "You are hungry.", RGB::named(rltk::ORANGE)),
+                _ => (HungerState::Starving, "You are starving!", RGB::named(rltk::RED)),
+            };
+            clock.state = next;
+            clock.duration = next.duration();
+            if is_player {
+                log.push(run_stats.turns, LogCategory::System, colour, message);
+            }
+        }
+    }
+}
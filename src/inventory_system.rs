@@ -0,0 +1,252 @@
+use rltk::RGB;
+use specs::prelude::*;
+use super::combat::attribute_bonus;
+use super::{AreaOfEffect, Attributes, CombatStats, Confusion, Consumable, Equippable, Equipped, GameLog, HungerClock, InBackpack,
+    InflictsDamage, LogCategory, Map, Monster, Name, Position, ProvidesFood, ProvidesHealing, ReceiveHealing, RunStats, Skills,
+    SufferDamage, WantsToDropItem, WantsToPickupItem, WantsToRemoveItem, WantsToUseItem};
+
+pub struct ItemCollectionSystem {}
+
+impl<'a> System<'a> for ItemCollectionSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        ReadExpect<'a, RunStats>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, WantsToPickupItem>,
+                        WriteStorage<'a, Position>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, InBackpack>
+                      );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, run_stats, mut log, mut wants_pickup, mut positions, names, mut backpack) = data;
+
+        for pickup in wants_pickup.join() {
+            positions.remove(pickup.item);
+            backpack.insert(pickup.item, InBackpack { owner: pickup.collected_by }).expect("Unable to insert backpack entry");
+
+            if pickup.collected_by == *player_entity {
+                if let Some(name) = names.get(pickup.item) {
+                    log.push(run_stats.turns, LogCategory::Item, RGB::named(rltk::WHITE), format!("You pick up the {}.", name.name));
+                }
+            }
+        }
+
+        wants_pickup.clear();
+    }
+}
+
+pub struct ItemDropSystem {}
+
+impl<'a> System<'a> for ItemDropSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        ReadExpect<'a, RunStats>,
+                        WriteExpect<'a, GameLog>,
+                        Entities<'a>,
+                        WriteStorage<'a, WantsToDropItem>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, Position>,
+                        WriteStorage<'a, InBackpack>
+                      );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, run_stats, mut log, entities, mut wants_drop, names, mut positions, mut backpack) = data;
+
+        for (entity, to_drop) in (&entities, &wants_drop).join() {
+            let dropper_pos = match positions.get(entity) {
+                Some(pos) => Position { x: pos.x, y: pos.y },
+                None => continue,
+            };
+            positions.insert(to_drop.item, dropper_pos).expect("Unable to insert position");
+            backpack.remove(to_drop.item);
+
+            if entity == *player_entity {
+                if let Some(name) = names.get(to_drop.item) {
+                    log.push(run_stats.turns, LogCategory::Item, RGB::named(rltk::WHITE), format!("You drop the {}.", name.name));
+                }
+            }
+        }
+
+        wants_drop.clear();
+    }
+}
+
+pub struct ItemUseSystem {}
+
+impl<'a> System<'a> for ItemUseSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, RunStats>,
+                        WriteExpect<'a, GameLog>,
+                        ReadExpect<'a, Map>,
+                        Entities<'a>,
+                        WriteStorage<'a, WantsToUseItem>,
+                        ReadStorage<'a, CombatStats>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, Monster>,
+                        ReadStorage<'a, Consumable>,
+                        ReadStorage<'a, ProvidesHealing>,
+                        WriteStorage<'a, ReceiveHealing>,
+                        ReadStorage<'a, InflictsDamage>,
+                        WriteStorage<'a, SufferDamage>,
+                        ReadStorage<'a, AreaOfEffect>,
+                        WriteStorage<'a, Confusion>,
+                        ReadStorage<'a, Equippable>,
+                        WriteStorage<'a, Equipped>,
+                        WriteStorage<'a, InBackpack>,
+                        ReadStorage<'a, Attributes>,
+                        ReadStorage<'a, Skills>,
+                        ReadStorage<'a, ProvidesFood>,
+                        WriteStorage<'a, HungerClock>
+                      );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, mut run_stats, mut log, map, entities, mut wants_use, combat_stats, names, monsters,
+            consumables, healing, mut receive_healing, inflict_damage, mut suffer_damage, aoe, mut confused, equippable,
+            mut equipped, mut backpack, attributes, skills, food, mut hunger_clocks) = data;
+
+        for (entity, useitem) in (&entities, &wants_use).join() {
+            let item_name = names.get(useitem.item).map(|n| n.name.clone()).unwrap_or_else(|| "item".to_string());
+
+            // Using equipment puts it on, swapping out whatever already fills that slot
+            if let Some(can_equip) = equippable.get(useitem.item) {
+                let slot = can_equip.slot;
+                let to_unequip: Vec<Entity> = (&entities, &equipped).join()
+                    .filter(|(_item, already)| already.owner == entity && already.slot == slot)
+                    .map(|(item, _already)| item)
+                    .collect();
+                for item in to_unequip {
+                    equipped.remove(item);
+                    backpack.insert(item, InBackpack { owner: entity }).expect("Unable to insert backpack entry");
+                    if entity == *player_entity {
+                        let name = names.get(item).map(|n| n.name.clone()).unwrap_or_else(|| "item".to_string());
+                        log.push(run_stats.turns, LogCategory::Item, RGB::named(rltk::WHITE), format!("You unequip the {}.", name));
+                    }
+                }
+
+                equipped.insert(useitem.item, Equipped { owner: entity, slot }).expect("Unable to equip item");
+                backpack.remove(useitem.item);
+                if entity == *player_entity {
+                    log.push(run_stats.turns, LogCategory::Item, RGB::named(rltk::WHITE), format!("You equip the {}.", item_name));
+                }
+                continue;
+            }
+            let target_name = |target: Entity| names.get(target).map(|n| n.name.clone()).unwrap_or_else(|| "it".to_string());
+
+            // Untargeted items affect their user; targeted ones whatever stands on the chosen
+            // tile, or within their blast radius of it
+            let mut targets: Vec<Entity> = Vec::new();
+            match useitem.target {
+                None => targets.push(entity),
+                Some(target) => match aoe.get(useitem.item) {
+                    None => {
+                        let idx = map.xy_idx(target.x, target.y);
+                        targets.extend(map.tile_content[idx].iter());
+                    }
+                    Some(area_effect) => {
+                        let mut blast_tiles = rltk::field_of_view(target, area_effect.radius, &*map);
+                        blast_tiles.retain(|p| p.x > 0 && p.x < map.width - 1 && p.y > 0 && p.y < map.height - 1);
+                        for tile in blast_tiles.iter() {
+                            let idx = map.xy_idx(tile.x, tile.y);
+                            targets.extend(map.tile_content[idx].iter());
+                        }
+                    }
+                },
+            }
+            targets.retain(|t| combat_stats.get(*t).is_some());
+
+            if food.get(useitem.item).is_some() {
+                for target in targets.iter() {
+                    if let Some(clock) = hunger_clocks.get_mut(*target) {
+                        *clock = HungerClock::well_fed();
+                        if *target == *player_entity {
+                            log.push(run_stats.turns, LogCategory::Item, RGB::named(rltk::GREEN),
+                                format!("You eat the {}.", item_name));
+                        }
+                    }
+                }
+            }
+
+            if let Some(healer) = healing.get(useitem.item) {
+                for target in targets.iter() {
+                    ReceiveHealing::new_healing(&mut receive_healing, *target, healer.amount);
+                    if entity == *player_entity {
+                        let message = if *target == entity {
+                            format!("You use the {}, healing {} hp.", item_name, healer.amount)
+                        } else {
+                            format!("You use the {} on {}, healing {} hp.", item_name, target_name(*target), healer.amount)
+                        };
+                        log.push(run_stats.turns, LogCategory::Item, RGB::named(rltk::GREEN), message);
+                    }
+                }
+            }
+
+            if let Some(damage) = inflict_damage.get(useitem.item) {
+                // A sharper mind and practice with magic both get more out of a scroll
+                let intelligence = attributes.get(entity).map(|a| attribute_bonus(a.intelligence)).unwrap_or(0);
+                let magic = skills.get(entity).map(|s| s.magic).unwrap_or(0);
+                let amount = i32::max(1, damage.damage + intelligence + magic);
+                for target in targets.iter() {
+                    SufferDamage::new_damage(&mut suffer_damage, *target, amount, entity);
+                    if *target == *player_entity {
+                        run_stats.last_attacker = Some(item_name.clone());
+                    }
+                    if entity == *player_entity {
+                        log.push(run_stats.turns, LogCategory::Combat, RGB::named(rltk::ORANGE),
+                            format!("You use {} on {}, inflicting {} hp.", item_name, target_name(*target), amount));
+                    }
+                }
+            }
+
+            if let Some(confusion) = confused.get(useitem.item).map(|c| c.turns) {
+                // Only monsters have a turn for confusion to take away
+                for target in targets.iter().filter(|t| monsters.get(**t).is_some()) {
+                    confused.insert(*target, Confusion { turns: confusion }).expect("Unable to insert status");
+                    if entity == *player_entity {
+                        log.push(run_stats.turns, LogCategory::Combat, RGB::named(rltk::MAGENTA),
+                            format!("You use {} on {}, confusing them.", item_name, target_name(*target)));
+                    }
+                }
+            }
+
+            if consumables.get(useitem.item).is_some() {
+                entities.delete(useitem.item).expect("Delete failed");
+            }
+        }
+
+        wants_use.clear();
+    }
+}
+
+pub struct ItemRemoveSystem {}
+
+impl<'a> System<'a> for ItemRemoveSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        ReadExpect<'a, RunStats>,
+                        WriteExpect<'a, GameLog>,
+                        Entities<'a>,
+                        WriteStorage<'a, WantsToRemoveItem>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, Equipped>,
+                        WriteStorage<'a, InBackpack>
+                      );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, run_stats, mut log, entities, mut wants_remove, names, mut equipped, mut backpack) = data;
+
+        for (entity, to_remove) in (&entities, &wants_remove).join() {
+            equipped.remove(to_remove.item);
+            backpack.insert(to_remove.item, InBackpack { owner: entity }).expect("Unable to insert backpack entry");
+
+            if entity == *player_entity {
+                if let Some(name) = names.get(to_remove.item) {
+                    log.push(run_stats.turns, LogCategory::Item, RGB::named(rltk::WHITE), format!("You unequip the {}.", name.name));
+                }
+            }
+        }
+
+        wants_remove.clear();
+    }
+}
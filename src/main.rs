@@ -1,5 +1,6 @@
-use rltk::{GameState, Rltk, RGB, Point};
+use rltk::{GameState, Rltk, RGB, Point, RandomNumberGenerator};
 use specs::prelude::*;
+use specs::saveload::{SimpleMarker, SimpleMarkerAllocator};
 
 mod components;
 pub use components::*;
@@ -19,76 +20,547 @@ mod melee_combat_system;
 pub use melee_combat_system::*;
 mod damage_system;
 pub use damage_system::*;
+mod spawner;
+mod run_stats;
+pub use run_stats::RunStats;
+mod gamelog;
+pub use gamelog::{GameLog, LogCategory, LogEntry};
+mod gui;
+mod inventory_system;
+use inventory_system::{ItemCollectionSystem, ItemDropSystem, ItemRemoveSystem, ItemUseSystem};
+mod status_effect_system;
+use status_effect_system::StatusEffectSystem;
+mod hunger_system;
+use hunger_system::HungerSystem;
+mod saveload_system;
+mod settings;
+pub use settings::Settings;
+mod map_builders;
+mod raws;
+mod random_table;
+pub use random_table::RandomTable;
+mod combat;
+
+/// How long each map generation snapshot stays on screen.
+const MAPGEN_FRAME_MS: f32 = 100.0;
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum RunState {
-    Running,
-    Paused,
+    PreRun,
+    AwaitingInput,
+    PlayerTurn,
+    MonsterTurn,
+    ShowInventory,
+    ShowDropItem,
+    ShowRemoveItem,
+    LevelUp { selection: usize },
+    ShowTargeting { range: i32, item: Entity, cursor: Point },
+    MainMenu { menu_selection: gui::MainMenuSelection },
+    SettingsMenu { selection: usize },
+    SaveGame,
+    NextLevel,
+    /// Plays back the snapshots taken while the level was built, then starts play.
+    MapGeneration { frame: usize },
+    GameOver,
 }
 
 pub struct State {
     pub ecs: World,
     pub run_state: RunState,
+    /// Shown under the main menu, e.g. when a save fails to load.
+    pub menu_message: Option<String>,
+    /// Snapshots from the last map built, waiting to be replayed.
+    pub mapgen_history: Vec<Map>,
+    mapgen_timer: f32,
+    /// A rest, explore or travel command still in progress.
+    auto_action: Option<AutoAction>,
 }
 
 impl State {
-    fn run_systems(&mut self) {
+    /// Refreshes viewsheds and the spatial index so the next phase sees the world as it now is.
+    fn run_indexing_systems(&mut self) {
         let mut vis = VisibilitySystem {};
         vis.run_now(&self.ecs);
 
-        let mut ai = MonsterAI {};
-        ai.run_now(&self.ecs);
-
         let mut map_indexer = MapIndexingSystem{};
         map_indexer.run_now(&self.ecs);
+    }
 
+    /// Returns true if the player was killed.
+    fn run_combat_systems(&mut self) -> bool {
         let mut melee_combat_system = MeleeCombatSystem{};
         melee_combat_system.run_now(&self.ecs);
 
         let mut dmg_system = DamageSystem{};
         dmg_system.run_now(&self.ecs);
 
+        let player_died = delete_the_dead(&mut self.ecs);
+        if player_died {
+            // Death is permanent, so the run can't be continued from its last save
+            saveload_system::delete_save();
+        }
+        player_died
+    }
+
+    fn run_player_systems(&mut self) -> RunState {
+        self.ecs.fetch_mut::<RunStats>().turns += 1;
+
+        let mut pickup = ItemCollectionSystem{};
+        pickup.run_now(&self.ecs);
+
+        let mut drop_items = ItemDropSystem{};
+        drop_items.run_now(&self.ecs);
+
+        let mut use_items = ItemUseSystem{};
+        use_items.run_now(&self.ecs);
+
+        let mut remove_items = ItemRemoveSystem{};
+        remove_items.run_now(&self.ecs);
+
+        let mut hunger = HungerSystem{};
+        hunger.run_now(&self.ecs);
+
+        let player_died = self.run_combat_systems();
+        self.run_indexing_systems();
+        self.ecs.maintain();
+
+        if player_died {
+            RunState::GameOver
+        } else if self.level_up_pending() {
+            RunState::LevelUp { selection: 0 }
+        } else {
+            RunState::MonsterTurn
+        }
+    }
+
+    fn level_up_pending(&self) -> bool {
+        let player_entity = *self.ecs.fetch::<Entity>();
+        self.ecs.read_storage::<Experience>().get(player_entity).map(|exp| exp.unspent_levels > 0).unwrap_or(false)
+    }
+
+    /// Spends one pending level on `bonus`, then hands the turn to the monsters once every
+    /// level has been spent.
+    fn apply_level_up_bonus(&mut self, bonus: gui::LevelUpBonus) -> RunState {
+        let player_entity = *self.ecs.fetch::<Entity>();
+        if let Some(stats) = self.ecs.write_storage::<CombatStats>().get_mut(player_entity) {
+            match bonus {
+                gui::LevelUpBonus::Toughness => {
+                    stats.max_hp += 5;
+                    stats.current_hp += 5;
+                }
+                gui::LevelUpBonus::Strength => stats.power += 1,
+                gui::LevelUpBonus::Guard => stats.defence += 1,
+            }
+        }
+        if let Some(exp) = self.ecs.write_storage::<Experience>().get_mut(player_entity) {
+            exp.unspent_levels -= 1;
+        }
+
+        if self.level_up_pending() { RunState::LevelUp { selection: 0 } } else { RunState::MonsterTurn }
+    }
+
+    fn run_monster_systems(&mut self) -> RunState {
+        let mut ai = MonsterAI {};
+        ai.run_now(&self.ecs);
+
+        let mut status_effects = StatusEffectSystem {};
+        status_effects.run_now(&self.ecs);
+
+        let player_died = self.run_combat_systems();
+        self.run_indexing_systems();
         self.ecs.maintain();
+
+        if player_died { RunState::GameOver } else { RunState::AwaitingInput }
+    }
+
+    /// Throws away the current world and builds a fresh map, player and monsters.
+    /// Uses the seed from the settings if one was chosen, so the same seed and the same inputs
+    /// replay the same run. Every later roll draws from the RNG resource inserted here.
+    fn new_game(&mut self) {
+        let seed = self.ecs.fetch::<Settings>().seed.unwrap_or_else(|| RandomNumberGenerator::new().next_u64());
+        self.ecs.insert(RandomNumberGenerator::seeded(seed));
+
+        self.ecs.delete_all();
+        self.auto_action = None;
+
+        let (player_x, player_y) = self.generate_world_map(1);
+
+        let player_entity = spawner::player(&mut self.ecs, player_x, player_y);
+        self.ecs.insert(player_entity);
+        self.ecs.insert(RunStats { seed, ..Default::default() });
+
+        let mut log = GameLog::default();
+        log.push(0, LogCategory::System, RGB::named(rltk::CYAN), format!("Welcome to Rustly. This run's seed is {}.", seed));
+        self.ecs.insert(log);
+
+        self.run_state = self.after_map_generated();
+    }
+
+    /// Play starts straight away unless there is a generation replay to show first.
+    fn after_map_generated(&self) -> RunState {
+        if self.mapgen_history.is_empty() {
+            RunState::PreRun
+        } else {
+            RunState::MapGeneration { frame: 0 }
+        }
+    }
+
+    /// Builds and populates the map for `depth`, returning where the player should start.
+    fn generate_world_map(&mut self, depth: i32) -> (i32, i32) {
+        let mut rng = (*self.ecs.fetch::<RandomNumberGenerator>()).clone();
+
+        let (builder_kind, record_history) = {
+            let settings = self.ecs.fetch::<Settings>();
+            (settings.map_builder, settings.show_mapgen)
+        };
+        let mut builder = match builder_kind {
+            Some(kind) => map_builders::builder_for(kind, depth, record_history),
+            None => map_builders::random_builder(&mut rng, depth, record_history),
+        };
+        builder.build_map(&mut rng);
+        self.mapgen_history = builder.get_snapshot_history();
+
+        let spawn_table = self.ecs.fetch::<raws::RawMaster>().spawn_table(depth);
+        let mut spawned = 0;
+        for region in builder.get_spawn_regions().iter() {
+            spawner::spawn_region(&mut self.ecs, &mut rng, &spawn_table, region, depth, &mut spawned);
+        }
+
+        let start_pos = builder.get_starting_position();
+        self.ecs.insert(Point::new(start_pos.0, start_pos.1));
+        self.ecs.insert(builder.get_map());
+        self.ecs.insert(rng);
+
+        start_pos
+    }
+
+    /// Everything on the current level except the player and what they carry or wear.
+    fn entities_to_remove_on_level_change(&mut self) -> Vec<Entity> {
+        let entities = self.ecs.entities();
+        let player = self.ecs.read_storage::<Player>();
+        let backpack = self.ecs.read_storage::<InBackpack>();
+        let equipped = self.ecs.read_storage::<Equipped>();
+        let player_entity = self.ecs.fetch::<Entity>();
+
+        entities
+            .join()
+            .filter(|entity| player.get(*entity).is_none())
+            .filter(|entity| backpack.get(*entity).map(|pack| pack.owner != *player_entity).unwrap_or(true))
+            .filter(|entity| equipped.get(*entity).map(|worn| worn.owner != *player_entity).unwrap_or(true))
+            .collect()
+    }
+
+    fn goto_next_level(&mut self) {
+        let to_delete = self.entities_to_remove_on_level_change();
+        self.ecs.delete_entities(&to_delete).expect("Unable to delete entity");
+
+        let depth = self.ecs.fetch::<Map>().depth + 1;
+        let (player_x, player_y) = self.generate_world_map(depth);
+
+        let player_entity = *self.ecs.fetch::<Entity>();
+        if let Some(pos) = self.ecs.write_storage::<Position>().get_mut(player_entity) {
+            pos.x = player_x;
+            pos.y = player_y;
+        }
+        if let Some(viewshed) = self.ecs.write_storage::<Viewshed>().get_mut(player_entity) {
+            viewshed.dirty = true;
+        }
+
+        let turn = self.ecs.fetch::<RunStats>().turns;
+        self.ecs.write_resource::<GameLog>().push(turn, LogCategory::System, RGB::named(rltk::MAGENTA),
+            format!("You descend to depth {}.", depth));
+    }
+
+    /// Steps through the generation snapshots at a steady pace. Any key skips to the game.
+    fn show_map_generation(&mut self, ctx: &mut Rltk, frame: usize) -> RunState {
+        draw_map(&self.mapgen_history[frame], ctx);
+        ctx.print_color(1, MAPHEIGHT as i32 + 1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK),
+            format!("Generating map: step {} of {}. Press any key to skip.", frame + 1, self.mapgen_history.len()));
+
+        self.mapgen_timer += ctx.frame_time_ms;
+        let mut next_frame = frame;
+        if self.mapgen_timer > MAPGEN_FRAME_MS {
+            self.mapgen_timer = 0.0;
+            next_frame += 1;
+        }
+
+        if ctx.key.is_some() || next_frame >= self.mapgen_history.len() {
+            self.mapgen_history.clear();
+            self.mapgen_timer = 0.0;
+            return RunState::PreRun;
+        }
+        RunState::MapGeneration { frame: next_frame }
+    }
+
+    fn main_menu(&mut self, ctx: &mut Rltk, selection: gui::MainMenuSelection) -> RunState {
+        let save_exists = saveload_system::does_save_exist();
+        match gui::main_menu(ctx, selection, self.menu_message.as_deref(), save_exists) {
+            gui::MainMenuResult::NoSelection { selected } => RunState::MainMenu { menu_selection: selected },
+            gui::MainMenuResult::Selected { selected } => {
+                self.menu_message = None;
+                match selected {
+                    gui::MainMenuSelection::NewGame => {
+                        self.new_game();
+                        self.run_state
+                    }
+                    gui::MainMenuSelection::Continue => match saveload_system::load_game(&mut self.ecs) {
+                        Ok(()) => {
+                            // Nothing carries over from the world that was just replaced
+                            self.auto_action = None;
+                            self.mapgen_history.clear();
+                            self.mapgen_timer = 0.0;
+                            RunState::PreRun
+                        }
+                        Err(e) => {
+                            self.menu_message = Some(e.to_string());
+                            RunState::MainMenu { menu_selection: selected }
+                        }
+                    },
+                    gui::MainMenuSelection::Settings => RunState::SettingsMenu { selection: 0 },
+                    gui::MainMenuSelection::Quit => {
+                        ctx.quit();
+                        RunState::MainMenu { menu_selection: selected }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Saves the run and heads back to the main menu. A failed save still leaves the game, with
+    /// the reason shown on the menu, so the player is never stuck in a run they asked to quit.
+    fn save_and_leave(&mut self) -> Result<(), saveload_system::SaveError> {
+        let result = saveload_system::save_game(&mut self.ecs);
+        match &result {
+            Ok(()) => self.run_state = RunState::MainMenu { menu_selection: gui::MainMenuSelection::Continue },
+            Err(e) => {
+                self.menu_message = Some(format!("Save failed. {}", e));
+                self.run_state = RunState::MainMenu { menu_selection: gui::MainMenuSelection::NewGame };
+            }
+        }
+        result
+    }
+
+    /// Closing the window mid-run saves it first, just like quitting with ESCAPE.
+    fn handle_window_events(&mut self, ctx: &mut Rltk) {
+        let mut close_requested = false;
+        rltk::INPUT.lock().for_each_message(|event| {
+            if event == rltk::BEvent::CloseRequested {
+                close_requested = true;
+            }
+        });
+
+        if close_requested {
+            let in_game = !matches!(self.run_state, RunState::MainMenu { .. } | RunState::SettingsMenu { .. } | RunState::GameOver);
+            if !in_game || self.save_and_leave().is_ok() {
+                ctx.quit();
+            } else if let Some(message) = &mut self.menu_message {
+                // Now on the menu, so closing again quits
+                message.push_str(" Close the window again to quit without saving.");
+            }
+        }
     }
 }
 
 impl GameState for State {
     fn tick(&mut self, ctx: &mut Rltk) {
         ctx.cls();
+        self.handle_window_events(ctx);
 
-        if self.run_state == RunState::Running {
-            self.run_systems();
-            delete_the_dead(&mut self.ecs);
-            self.run_state = RunState::Paused;
-        } else {
-            self.run_state = player_input(self, ctx);
+        match self.run_state {
+            RunState::MainMenu { menu_selection } => {
+                self.run_state = self.main_menu(ctx, menu_selection);
+                return;
+            }
+            RunState::SettingsMenu { selection } => {
+                self.run_state = match gui::settings_menu(self, ctx, selection) {
+                    gui::SettingsMenuResult::NoResponse { selected } => RunState::SettingsMenu { selection: selected },
+                    gui::SettingsMenuResult::Back => RunState::MainMenu { menu_selection: gui::MainMenuSelection::Settings },
+                };
+                return;
+            }
+            RunState::MapGeneration { frame } => {
+                self.run_state = self.show_map_generation(ctx, frame);
+                return;
+            }
+            RunState::GameOver => {
+                draw_game_over(&self.ecs, ctx);
+                match ctx.key {
+                    Some(rltk::VirtualKeyCode::Return) => self.new_game(),
+                    Some(rltk::VirtualKeyCode::Escape) => {
+                        self.run_state = RunState::MainMenu { menu_selection: gui::MainMenuSelection::NewGame };
+                    }
+                    _ => {}
+                }
+                return;
+            }
+            _ => {}
         }
 
+        draw_map(&self.ecs.fetch::<Map>(), ctx);
+        draw_entities(&self.ecs, ctx);
+        if self.run_state == RunState::AwaitingInput && self.auto_action.is_none() {
+            if let Some(path) = travel_path_to_mouse(&self.ecs, ctx) {
+                gui::draw_travel_path(&self.ecs, ctx, &path[1..]);
+            }
+        }
+        gui::draw_ui(&self.ecs, ctx);
+
+        self.run_state = match self.run_state {
+            RunState::PreRun => {
+                self.run_indexing_systems();
+                RunState::AwaitingInput
+            }
+            RunState::AwaitingInput => player_input(self, ctx),
+            RunState::PlayerTurn => self.run_player_systems(),
+            RunState::MonsterTurn => self.run_monster_systems(),
+            RunState::ShowInventory => {
+                match gui::show_inventory(self, ctx, "Use which item?") {
+                    (gui::ItemMenuResult::NoResponse, _) => RunState::ShowInventory,
+                    (gui::ItemMenuResult::Selected, Some(item)) => {
+                        let ranged = self.ecs.read_storage::<Ranged>().get(item).map(|r| r.range);
+                        match ranged {
+                            Some(range) => {
+                                let cursor = *self.ecs.fetch::<Point>();
+                                RunState::ShowTargeting { range, item, cursor }
+                            }
+                            None => {
+                                let player_entity = *self.ecs.fetch::<Entity>();
+                                let mut intent = self.ecs.write_storage::<WantsToUseItem>();
+                                intent.insert(player_entity, WantsToUseItem { item, target: None }).expect("Unable to insert intent");
+                                RunState::PlayerTurn
+                            }
+                        }
+                    }
+                    _ => RunState::AwaitingInput,
+                }
+            }
+            RunState::ShowDropItem => {
+                match gui::show_inventory(self, ctx, "Drop which item?") {
+                    (gui::ItemMenuResult::NoResponse, _) => RunState::ShowDropItem,
+                    (gui::ItemMenuResult::Selected, Some(item)) => {
+                        let player_entity = *self.ecs.fetch::<Entity>();
+                        let mut intent = self.ecs.write_storage::<WantsToDropItem>();
+                        intent.insert(player_entity, WantsToDropItem { item }).expect("Unable to insert intent");
+                        RunState::PlayerTurn
+                    }
+                    _ => RunState::AwaitingInput,
+                }
+            }
+            RunState::ShowRemoveItem => {
+                match gui::show_equipped(self, ctx, "Remove which item?") {
+                    (gui::ItemMenuResult::NoResponse, _) => RunState::ShowRemoveItem,
+                    (gui::ItemMenuResult::Selected, Some(item)) => {
+                        let player_entity = *self.ecs.fetch::<Entity>();
+                        let mut intent = self.ecs.write_storage::<WantsToRemoveItem>();
+                        intent.insert(player_entity, WantsToRemoveItem { item }).expect("Unable to insert intent");
+                        RunState::PlayerTurn
+                    }
+                    _ => RunState::AwaitingInput,
+                }
+            }
+            RunState::LevelUp { selection } => match gui::level_up(&self.ecs, ctx, selection) {
+                gui::LevelUpResult::NoResponse { selected } => RunState::LevelUp { selection: selected },
+                gui::LevelUpResult::Chosen { bonus } => self.apply_level_up_bonus(bonus),
+            },
+            RunState::ShowTargeting { range, item, mut cursor } => {
+                match gui::ranged_target(self, ctx, range, &mut cursor) {
+                    (gui::ItemMenuResult::Cancel, _) => RunState::AwaitingInput,
+                    (gui::ItemMenuResult::Selected, Some(target)) => {
+                        let player_entity = *self.ecs.fetch::<Entity>();
+                        let mut intent = self.ecs.write_storage::<WantsToUseItem>();
+                        intent.insert(player_entity, WantsToUseItem { item, target: Some(target) }).expect("Unable to insert intent");
+                        RunState::PlayerTurn
+                    }
+                    _ => RunState::ShowTargeting { range, item, cursor },
+                }
+            }
+            RunState::NextLevel => {
+                self.goto_next_level();
+                self.after_map_generated()
+            }
+            RunState::SaveGame => {
+                // Either way this lands on the main menu
+                let _ = self.save_and_leave();
+                self.run_state
+            }
+            RunState::MainMenu { .. } | RunState::SettingsMenu { .. } | RunState::MapGeneration { .. } | RunState::GameOver => {
+                self.run_state
+            }
+        };
+    }
+}
 
-        draw_map(&self.ecs, ctx);
+/// Reads `--seed <n>` (or `--seed=<n>`) from the command line.
+#[derive(Default)]
+struct CommandLine {
+    seed: Option<u64>,
+    /// Print every generation snapshot for this builder as text and exit, without a window.
+    dump_mapgen: Option<map_builders::MapBuilderKind>,
+}
 
-        let positions = self.ecs.read_storage::<Position>();
-        let renderables = self.ecs.read_storage::<Renderable>();
-        let map = self.ecs.fetch::<Map>();
+fn parse_args() -> Result<CommandLine, String> {
+    let mut command_line = CommandLine::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+        let mut value = || inline_value.clone().or_else(|| args.next()).ok_or(format!("{} needs a value", flag));
 
-        for (pos, render) in (&positions, &renderables).join() {
-            let idx = map.xy_idx(pos.x, pos.y);
-            if map.visible_tiles[idx] {
-                ctx.set(pos.x, pos.y, render.fg, render.bg, render.glyph);
+        match flag.as_str() {
+            "--seed" => {
+                let value = value()?;
+                command_line.seed = Some(value.parse::<u64>().map_err(|_| format!("Invalid seed: {}", value))?);
+            }
+            "--dump-mapgen" => {
+                let value = value()?;
+                let kind = map_builders::MapBuilderKind::from_arg_name(&value).ok_or_else(|| {
+                    let names: Vec<&str> = map_builders::MapBuilderKind::ALL.iter().map(|kind| kind.arg_name()).collect();
+                    format!("Unknown map builder: {}. Expected one of: {}", value, names.join(", "))
+                })?;
+                command_line.dump_mapgen = Some(kind);
             }
+            _ => return Err(format!("Unrecognised argument: {}", flag)),
         }
     }
+    Ok(command_line)
+}
+
+fn dump_map_generation(kind: map_builders::MapBuilderKind, seed: Option<u64>) {
+    let mut rng = RandomNumberGenerator::seeded(seed.unwrap_or_else(|| RandomNumberGenerator::new().next_u64()));
+    let mut builder = map_builders::builder_for(kind, 1, true);
+    builder.build_map(&mut rng);
+    print!("{}", map_builders::history_to_text(&builder.get_snapshot_history()));
 }
 
 fn main() -> rltk::BError {
-    let mut rng = rltk::RandomNumberGenerator::new();
+    let command_line = parse_args()?;
+    if let Some(kind) = command_line.dump_mapgen {
+        dump_map_generation(kind, command_line.seed);
+        return Ok(());
+    }
+
+    let raws = match raws::load_raws(raws::RAWS_PATH) {
+        Ok(raws) => raws,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
     use rltk::RltkBuilder;
     let context = RltkBuilder::simple80x50().with_title("Rustly").build()?;
 
+    rltk::INPUT.lock().activate_event_queue();
+
     let mut gs = State {
         ecs: World::new(),
-        run_state: RunState::Running,
+        run_state: RunState::MainMenu { menu_selection: gui::MainMenuSelection::NewGame },
+        menu_message: None,
+        mapgen_history: Vec::new(),
+        mapgen_timer: 0.0,
+        auto_action: None,
     };
     gs.ecs.register::<Position>();
     gs.ecs.register::<Renderable>();
@@ -100,86 +572,53 @@ fn main() -> rltk::BError {
     gs.ecs.register::<CombatStats>();
     gs.ecs.register::<WantsToMelee>();
     gs.ecs.register::<SufferDamage>();
-
-    let map = Map::new(&mut rng);
-
-    for (i, room) in map.rooms.iter().skip(1).enumerate() {
-        let (x, y) = room.center();
-
-        let glyph: rltk::FontCharType;
-        let name: String;
-        let roll = rng.roll_dice(1, 2);
-        match roll {
-            1 => {glyph = rltk::to_cp437('g'); name = "Goblin".to_string();},
-            _ => {glyph = rltk::to_cp437('o'); name= "Orc".to_string();},
-        }
-
-        gs.ecs
-            .create_entity()
-            .with(Monster {})
-            .with(Name{ name: format!("{} #{}", name , i) })
-            .with(Position { x, y })
-            .with(Renderable {
-                glyph,
-                fg: RGB::named(rltk::RED),
-                bg: RGB::named(rltk::BLACK),
-            })
-            .with(Viewshed {
-                range: 8,
-                visible_tiles: Vec::new(),
-                dirty: true,
-            })
-            .with(BlocksTile{})
-            .with(CombatStats{
-                max_hp: 16,
-                current_hp: 16,
-                defence: 1,
-                power: 2
-            })
-            .build();
-    }
-
-    let (player_x, player_y) = map.start_pos;
-    gs.ecs.insert(Point::new(player_x, player_y));
-    gs.ecs.insert(map);
-
-    gs.ecs
-        .create_entity()
-        .with(Position {
-            x: player_x,
-            y: player_y,
-        })
-        .with(Renderable {
-            glyph: rltk::to_cp437('@'),
-            fg: RGB::named(rltk::YELLOW),
-            bg: RGB::named(rltk::BLACK),
-        })
-        .with(Player {})
-        .with(Viewshed {
-            visible_tiles: Vec::new(),
-            range: 8,
-            dirty: true,
-        })
-        .with(Name{ name: "Player".to_string() })
-        .with(CombatStats{
-                max_hp: 30,
-                current_hp: 30,
-                defence: 2,
-                power: 5
-            })
-        .build();
+    gs.ecs.register::<Item>();
+    gs.ecs.register::<InBackpack>();
+    gs.ecs.register::<WantsToPickupItem>();
+    gs.ecs.register::<WantsToDropItem>();
+    gs.ecs.register::<ReceiveHealing>();
+    gs.ecs.register::<Consumable>();
+    gs.ecs.register::<ProvidesHealing>();
+    gs.ecs.register::<WantsToUseItem>();
+    gs.ecs.register::<Ranged>();
+    gs.ecs.register::<InflictsDamage>();
+    gs.ecs.register::<AreaOfEffect>();
+    gs.ecs.register::<Confusion>();
+    gs.ecs.register::<Equippable>();
+    gs.ecs.register::<Equipped>();
+    gs.ecs.register::<MeleePowerBonus>();
+    gs.ecs.register::<DefenceBonus>();
+    gs.ecs.register::<WantsToRemoveItem>();
+    gs.ecs.register::<Experience>();
+    gs.ecs.register::<ExperienceValue>();
+    gs.ecs.register::<Attributes>();
+    gs.ecs.register::<Skills>();
+    gs.ecs.register::<DamageDice>();
+    gs.ecs.register::<HungerClock>();
+    gs.ecs.register::<ProvidesFood>();
+    gs.ecs.register::<SimpleMarker<SerializeMe>>();
+    gs.ecs.register::<SerializationHelper>();
+
+    gs.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+    gs.ecs.insert(Settings { seed: command_line.seed, ..Default::default() });
+    gs.ecs.insert(raws);
+
+    if saveload_system::does_save_exist() {
+        gs.run_state = RunState::MainMenu { menu_selection: gui::MainMenuSelection::Continue };
+    }
 
     rltk::main_loop(context, gs)
 }
 
-fn draw_map(ecs: &World, ctx: &mut Rltk) {
-    let map = ecs.fetch::<Map>();
+fn draw_map(map: &Map, ctx: &mut Rltk) {
 
     let floor_fg = RGB::from_f32(0.5, 0.5, 0.5);
     let wall_fg = RGB::from_f32(0., 1., 0.);
     let bg = RGB::from_f32(0., 0., 0.);
+    let stairs_fg = RGB::from_f32(0., 1., 1.);
     let floor_glyph = rltk::to_cp437('.');
     let wall_glyph = rltk::to_cp437('#');
+    let stairs_glyph = rltk::to_cp437('>');
 
     let mut x = 0;
     let mut y = 0;
@@ -187,6 +626,7 @@ fn draw_map(ecs: &World, ctx: &mut Rltk) {
         let (mut fg, glyph) = match tile {
             TileType::Floor => (floor_fg, floor_glyph),
             TileType::Wall => (wall_fg, wall_glyph),
+            TileType::DownStairs => (stairs_fg, stairs_glyph),
         };
 
         if map.revealed_tiles[idx] {
@@ -197,23 +637,75 @@ fn draw_map(ecs: &World, ctx: &mut Rltk) {
         }
 
         x += 1;
-        if x > 79 {
+        if x > map.width - 1 {
             x = 0;
             y += 1;
         }
     }
 }
 
-pub fn delete_the_dead(ecs: &mut World) {
+fn draw_entities(ecs: &World, ctx: &mut Rltk) {
+    let positions = ecs.read_storage::<Position>();
+    let renderables = ecs.read_storage::<Renderable>();
+    let map = ecs.fetch::<Map>();
+
+    let mut data = (&positions, &renderables).join().collect::<Vec<_>>();
+    data.sort_by_key(|(_pos, render)| std::cmp::Reverse(render.render_order));
+    for (pos, render) in data.iter() {
+        let idx = map.xy_idx(pos.x, pos.y);
+        if map.visible_tiles[idx] {
+            ctx.set(pos.x, pos.y, render.fg, render.bg, render.glyph);
+        }
+    }
+}
+
+fn draw_game_over(ecs: &World, ctx: &mut Rltk) {
+    let stats = ecs.fetch::<RunStats>();
+    let cause = stats.cause_of_death.as_deref().unwrap_or("Unknown causes");
+
+    ctx.print_color_centered(15, RGB::named(rltk::RED), RGB::named(rltk::BLACK), "You have died.");
+    ctx.print_color_centered(18, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), format!("Cause of death: {}", cause));
+    ctx.print_color_centered(19, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), format!("You survived {} turns.", stats.turns));
+    ctx.print_color_centered(20, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), format!("You slew {} monsters.", stats.kills));
+    ctx.print_color_centered(21, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), format!("Seed: {}", stats.seed));
+    ctx.print_color_centered(23, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Press ENTER to start a new run.");
+    ctx.print_color_centered(24, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Press ESCAPE to return to the main menu.");
+}
+
+/// Removes everything that has run out of hit points. The player is never deleted;
+/// instead their death is recorded and reported by returning true.
+pub fn delete_the_dead(ecs: &mut World) -> bool {
     let mut dead: Vec<Entity> = Vec::new();
+    let mut player_died = false;
 
     {
         let combat_stats = ecs.read_storage::<CombatStats>();
+        let players = ecs.read_storage::<Player>();
+        let monsters = ecs.read_storage::<Monster>();
+        let names = ecs.read_storage::<Name>();
         let entities = ecs.entities();
-
-        for (entity, stats) in (&entities, &combat_stats).join() {
-            if stats.current_hp < 1 {
-                dead.push(entity);
+        let mut stats = ecs.write_resource::<RunStats>();
+        let mut log = ecs.write_resource::<GameLog>();
+
+        for (entity, combat) in (&entities, &combat_stats).join() {
+            if combat.current_hp < 1 {
+                if players.get(entity).is_some() {
+                    player_died = true;
+                    let cause = match &stats.last_attacker {
+                        Some(attacker) => format!("Slain by {}", attacker),
+                        None => "Unknown causes".to_string(),
+                    };
+                    log.push(stats.turns, LogCategory::Death, RGB::named(rltk::RED), format!("You die. {}.", cause));
+                    stats.cause_of_death = Some(cause);
+                } else {
+                    if let Some(name) = names.get(entity) {
+                        log.push(stats.turns, LogCategory::Death, RGB::named(rltk::ORANGE), format!("{} is dead.", name.name));
+                    }
+                    if monsters.get(entity).is_some() {
+                        stats.kills += 1;
+                    }
+                    dead.push(entity);
+                }
             }
         }
     }
@@ -221,4 +713,6 @@ pub fn delete_the_dead(ecs: &mut World) {
     for victim in dead {
         ecs.delete_entity(victim).expect("could not delete entity")
     }
+
+    player_died
 }
@@ -1,15 +1,20 @@
 use super::Rect;
-use rltk::{Algorithm2D, BaseMap, Point, RandomNumberGenerator};
+use rltk::{Algorithm2D, BaseMap, Point};
 use specs::Entity;
-use std::cmp::{max, min};
+use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq, Copy, Clone)]
+pub const MAPWIDTH: usize = 80;
+pub const MAPHEIGHT: usize = 43;
+pub const MAPCOUNT: usize = MAPWIDTH * MAPHEIGHT;
+
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum TileType {
     Wall,
     Floor,
+    DownStairs,
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize, Clone)]
 pub struct Map {
     pub tiles: Vec<TileType>,
     pub width: i32,
@@ -18,62 +23,29 @@ pub struct Map {
     pub revealed_tiles: Vec<bool>,
     pub visible_tiles: Vec<bool>,
     pub rooms: Vec<Rect>,
+    // Derived from the entities on the map, so rebuilt by MapIndexingSystem after a load
+    #[serde(skip)]
     pub blocked: Vec<bool>,
+    #[serde(skip)]
     pub tile_content: Vec<Vec<Entity>>,
+    pub depth: i32,
 }
 
 impl Map {
-    pub fn new(rng: &mut RandomNumberGenerator) -> Self {
-        const MAX_ROOMS: i32 = 30;
-        const MIN_SIZE: i32 = 6;
-        const MAX_SIZE: i32 = 10;
-
-        let mut map = Map {
-            tiles: vec![TileType::Wall; 80 * 50],
-            width: 80,
-            height: 50,
+    /// A solid map of wall for a builder to carve into.
+    pub fn new(depth: i32) -> Self {
+        Map {
+            tiles: vec![TileType::Wall; MAPCOUNT],
+            width: MAPWIDTH as i32,
+            height: MAPHEIGHT as i32,
             start_pos: (0, 0),
-            revealed_tiles: vec![false; 80 * 50],
-            visible_tiles: vec![false; 80 * 50],
+            revealed_tiles: vec![false; MAPCOUNT],
+            visible_tiles: vec![false; MAPCOUNT],
             rooms: Vec::new(),
-            blocked: vec![false; 80 * 50],
-            tile_content: vec![Vec::new(); 80 * 50],
-        };
-
-        for _i in 0..MAX_ROOMS {
-            let w = rng.range(MIN_SIZE, MAX_SIZE);
-            let h = rng.range(MIN_SIZE, MAX_SIZE);
-            let x = rng.roll_dice(1, map.width - w - 1) - 1;
-            let y = rng.roll_dice(1, map.height - h - 1) - 1;
-            let new_room = Rect::new(x, y, w, h);
-            let mut ok = true;
-            for other_room in map.rooms.iter() {
-                if new_room.intersect(other_room) {
-                    ok = false
-                }
-            }
-            if ok {
-                map.apply_room_to_map(&new_room);
-
-                if map.rooms.is_empty() {
-                    map.start_pos = new_room.center();
-                } else {
-                    let (new_x, new_y) = new_room.center();
-                    let (prev_x, prev_y) = map.rooms[map.rooms.len() - 1].center();
-                    if rng.range(0, 2) == 1 {
-                        map.apply_horizontal_tunnel(prev_x, new_x, prev_y);
-                        map.apply_vertical_tunnel(prev_y, new_y, new_x);
-                    } else {
-                        map.apply_vertical_tunnel(prev_y, new_y, prev_x);
-                        map.apply_horizontal_tunnel(prev_x, new_x, new_y);
-                    }
-                }
-
-                map.rooms.push(new_room);
-            }
+            blocked: vec![false; MAPCOUNT],
+            tile_content: vec![Vec::new(); MAPCOUNT],
+            depth,
         }
-
-        map
     }
 
     pub fn xy_idx(&self, x: i32, y: i32) -> usize {
@@ -92,31 +64,42 @@ impl Map {
         }
     }
 
-    fn apply_room_to_map(&mut self, room: &Rect) {
-        for y in room.y1 + 1..=room.y2 {
-            for x in room.x1 + 1..=room.x2 {
-                let idx = self.xy_idx(x, y);
-                self.tiles[idx] = TileType::Floor;
-            }
-        }
-    }
+    /// The neighbours of `idx` that `passable` allows, with diagonal steps costing a little more.
+    fn exits_where(&self, idx: usize, passable: impl Fn(i32, i32) -> bool) -> rltk::SmallVec<[(usize, f32); 10]> {
+        let mut exits = rltk::SmallVec::new();
+        let x = idx as i32 % self.width;
+        let y = idx as i32 / self.width;
+        let w = self.width as usize;
 
-    fn apply_horizontal_tunnel(&mut self, x1: i32, x2: i32, y: i32) {
-        for x in min(x1, x2)..=max(x1, x2) {
-            let idx = self.xy_idx(x, y);
-            if idx > 0 && idx < self.width as usize * self.height as usize {
-                self.tiles[idx] = TileType::Floor;
-            }
-        }
-    }
+        // Cardinal directions
+        if passable(x - 1, y) {
+            exits.push((idx - 1, 1.0))
+        };
+        if passable(x + 1, y) {
+            exits.push((idx + 1, 1.0))
+        };
+        if passable(x, y - 1) {
+            exits.push((idx - w, 1.0))
+        };
+        if passable(x, y + 1) {
+            exits.push((idx + w, 1.0))
+        };
 
-    fn apply_vertical_tunnel(&mut self, y1: i32, y2: i32, x: i32) {
-        for y in min(y1, y2)..=max(y1, y2) {
-            let idx = self.xy_idx(x, y);
-            if idx > 0 && idx < self.width as usize * self.height as usize {
-                self.tiles[idx] = TileType::Floor;
-            }
+        // Diagonals
+        if passable(x - 1, y - 1) {
+            exits.push(((idx - w) - 1, 1.45));
+        }
+        if passable(x + 1, y - 1) {
+            exits.push(((idx - w) + 1, 1.45));
+        }
+        if passable(x - 1, y + 1) {
+            exits.push(((idx + w) - 1, 1.45));
         }
+        if passable(x + 1, y + 1) {
+            exits.push(((idx + w) + 1, 1.45));
+        }
+
+        exits
     }
 
     fn is_exit_valid(&self, x: i32, y: i32) -> bool {
@@ -140,46 +123,104 @@ impl BaseMap for Map {
     }
 
     fn get_available_exits(&self, idx: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
-        let mut exits = rltk::SmallVec::new();
-        let x = idx as i32 % self.width;
-        let y = idx as i32 / self.width;
+        self.exits_where(idx, |x, y| self.is_exit_valid(x, y))
+    }
+
+    fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
         let w = self.width as usize;
+        let p1 = Point::new(idx1 % w, idx1 / w);
+        let p2 = Point::new(idx2 % w, idx2 / w);
+        rltk::DistanceAlg::Pythagoras.distance2d(p1, p2)
+    }
+}
 
-        // Cardinal directions
-        if self.is_exit_valid(x - 1, y) {
-            exits.push((idx - 1, 1.0))
-        };
-        if self.is_exit_valid(x + 1, y) {
-            exits.push((idx + 1, 1.0))
-        };
-        if self.is_exit_valid(x, y - 1) {
-            exits.push((idx - w, 1.0))
-        };
-        if self.is_exit_valid(x, y + 1) {
-            exits.push((idx + w, 1.0))
-        };
+/// The map as the player knows it. Unexplored tiles can't be walked through, and only blockers
+/// the player can currently see are in the way, so a path never bends around a hidden monster.
+struct KnownMap<'a>(&'a Map);
 
-        // Diagonals
-        if self.is_exit_valid(x - 1, y - 1) {
-            exits.push(((idx - w) - 1, 1.45));
-        }
-        if self.is_exit_valid(x + 1, y - 1) {
-            exits.push(((idx - w) + 1, 1.45));
+impl KnownMap<'_> {
+    fn is_known_passable(&self, x: i32, y: i32) -> bool {
+        let map = self.0;
+        if x < 1 || x > map.width - 1 || y < 1 || y > map.height - 1 {
+            return false;
         }
-        if self.is_exit_valid(x - 1, y + 1) {
-            exits.push(((idx + w) - 1, 1.45));
+        let idx = map.xy_idx(x, y);
+        map.revealed_tiles[idx] && map.tiles[idx] != TileType::Wall && !(map.visible_tiles[idx] && map.blocked[idx])
+    }
+}
+
+impl BaseMap for KnownMap<'_> {
+    fn is_opaque(&self, idx: usize) -> bool {
+        self.0.is_opaque(idx)
+    }
+
+    fn get_available_exits(&self, idx: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
+        self.0.exits_where(idx, |x, y| self.is_known_passable(x, y))
+    }
+
+    fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
+        self.0.get_pathing_distance(idx1, idx2)
+    }
+}
+
+impl Map {
+    /// Plots a path from `start` to `end` through tiles the player has already seen, so
+    /// travelling never gives away the layout of somewhere unexplored. The path includes
+    /// `start`.
+    pub fn known_path(&self, start: usize, end: usize) -> Option<Vec<usize>> {
+        if !self.revealed_tiles[end] {
+            return None;
         }
-        if self.is_exit_valid(x + 1, y + 1) {
-            exits.push(((idx + w) + 1, 1.45));
+        let path = rltk::a_star_search(start as i32, end as i32, &KnownMap(self));
+        if path.success { Some(path.steps) } else { None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A revealed corridor along y = 5 from x = 1 to x = 9, with one blocker in the middle.
+    fn corridor_with_blocker(blocker_visible: bool) -> Map {
+        let mut map = Map::new(1);
+        for x in 1..10 {
+            let idx = map.xy_idx(x, 5);
+            map.tiles[idx] = TileType::Floor;
+            map.revealed_tiles[idx] = true;
         }
+        map.populate_blocked();
+        let blocker = map.xy_idx(5, 5);
+        map.blocked[blocker] = true;
+        map.visible_tiles[blocker] = blocker_visible;
+        map
+    }
 
-        exits
+    #[test]
+    fn known_path_ignores_blockers_the_player_cannot_see() {
+        let map = corridor_with_blocker(false);
+        let path = map.known_path(map.xy_idx(1, 5), map.xy_idx(9, 5)).expect("a path through the hidden blocker");
+        assert_eq!(path.len(), 9);
+        assert!(path.contains(&map.xy_idx(5, 5)));
     }
 
-    fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
-        let w = self.width as usize;
-        let p1 = Point::new(idx1 % w, idx1 / w);
-        let p2 = Point::new(idx2 % w, idx2 / w);
-        rltk::DistanceAlg::Pythagoras.distance2d(p1, p2)
+    #[test]
+    fn known_path_avoids_blockers_in_view() {
+        let map = corridor_with_blocker(true);
+        assert!(map.known_path(map.xy_idx(1, 5), map.xy_idx(9, 5)).is_none());
+    }
+
+    #[test]
+    fn known_path_stays_on_explored_ground() {
+        let mut map = corridor_with_blocker(false);
+        // Floor alongside the corridor that the player has never seen
+        for x in 1..10 {
+            let idx = map.xy_idx(x, 6);
+            map.tiles[idx] = TileType::Floor;
+        }
+        let hidden_end = map.xy_idx(9, 6);
+        assert!(map.known_path(map.xy_idx(1, 5), hidden_end).is_none());
+
+        let path = map.known_path(map.xy_idx(1, 5), map.xy_idx(9, 5)).unwrap();
+        assert!(path.iter().all(|idx| map.revealed_tiles[*idx]));
     }
 }
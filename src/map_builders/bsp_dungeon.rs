@@ -0,0 +1,113 @@
+use super::common::{SnapshotHistory, cull_unreachable, apply_horizontal_tunnel, apply_room_to_map, apply_vertical_tunnel, room_spawn_regions};
+use super::MapBuilder;
+use super::super::{Map, Rect, TileType};
+use rltk::RandomNumberGenerator;
+
+const ATTEMPTS: i32 = 240;
+/// Empty tiles kept around each room so neighbours never share a wall.
+const ROOM_GAP: i32 = 2;
+
+/// Repeatedly splits the map into quarters and tries to fit a room into one of the pieces,
+/// then joins the rooms left to right. Gives tidier, denser layouts than scattering rooms.
+pub struct BspDungeonBuilder {
+    map: Map,
+    history: SnapshotHistory,
+    rects: Vec<Rect>,
+}
+
+impl BspDungeonBuilder {
+    pub fn new(depth: i32, record_history: bool) -> Self {
+        BspDungeonBuilder { map: Map::new(depth), history: SnapshotHistory::new(record_history), rects: Vec::new() }
+    }
+
+    fn add_subrects(&mut self, rect: &Rect) {
+        let width = i32::abs(rect.x1 - rect.x2);
+        let height = i32::abs(rect.y1 - rect.y2);
+        let half_width = i32::max(width / 2, 1);
+        let half_height = i32::max(height / 2, 1);
+
+        self.rects.push(Rect::new(rect.x1, rect.y1, half_width, half_height));
+        self.rects.push(Rect::new(rect.x1, rect.y1 + half_height, half_width, half_height));
+        self.rects.push(Rect::new(rect.x1 + half_width, rect.y1, half_width, half_height));
+        self.rects.push(Rect::new(rect.x1 + half_width, rect.y1 + half_height, half_width, half_height));
+    }
+
+    fn random_room_in(rng: &mut RandomNumberGenerator, rect: &Rect) -> Rect {
+        let rect_width = i32::abs(rect.x1 - rect.x2);
+        let rect_height = i32::abs(rect.y1 - rect.y2);
+        let w = i32::max(3, rng.roll_dice(1, i32::min(rect_width, 10)) - 1) + 1;
+        let h = i32::max(3, rng.roll_dice(1, i32::min(rect_height, 10)) - 1) + 1;
+        let x = rect.x1 + rng.roll_dice(1, 6) - 1;
+        let y = rect.y1 + rng.roll_dice(1, 6) - 1;
+        Rect::new(x, y, w, h)
+    }
+
+    fn room_fits(&self, room: &Rect) -> bool {
+        let padded = Rect::new(room.x1 - ROOM_GAP, room.y1 - ROOM_GAP, room.x2 - room.x1 + ROOM_GAP * 2,
+            room.y2 - room.y1 + ROOM_GAP * 2);
+        if padded.x1 < 0 || padded.y1 < 0 || padded.x2 > self.map.width - 2 || padded.y2 > self.map.height - 2 {
+            return false;
+        }
+        !self.map.rooms.iter().any(|other| padded.intersect(other))
+    }
+}
+
+impl MapBuilder for BspDungeonBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        let whole_map = Rect::new(1, 1, self.map.width - 3, self.map.height - 3);
+        self.rects.push(whole_map.clone());
+        self.add_subrects(&whole_map);
+
+        for _i in 0..ATTEMPTS {
+            let rect_idx = rng.range(0, self.rects.len() as i32) as usize;
+            let rect = self.rects[rect_idx].clone();
+            let room = Self::random_room_in(rng, &rect);
+
+            if self.room_fits(&room) {
+                apply_room_to_map(&mut self.map, &room);
+                self.map.rooms.push(room);
+                self.add_subrects(&rect);
+                self.history.record(&self.map);
+            }
+        }
+
+        let map = &mut self.map;
+        map.rooms.sort_by_key(|room| room.x1);
+
+        for i in 1..map.rooms.len() {
+            let (prev_x, prev_y) = map.rooms[i - 1].center();
+            let (new_x, new_y) = map.rooms[i].center();
+            if rng.range(0, 2) == 1 {
+                apply_horizontal_tunnel(map, prev_x, new_x, prev_y);
+                apply_vertical_tunnel(map, prev_y, new_y, new_x);
+            } else {
+                apply_vertical_tunnel(map, prev_y, new_y, prev_x);
+                apply_horizontal_tunnel(map, prev_x, new_x, new_y);
+            }
+            self.history.record(map);
+        }
+
+        map.start_pos = map.rooms[0].center();
+        cull_unreachable(map);
+        let (stairs_x, stairs_y) = map.rooms[map.rooms.len() - 1].center();
+        let stairs_idx = map.xy_idx(stairs_x, stairs_y);
+        map.tiles[stairs_idx] = TileType::DownStairs;
+        self.history.record(map);
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_snapshot_history(&self) -> Vec<Map> {
+        self.history.frames()
+    }
+
+    fn get_starting_position(&self) -> (i32, i32) {
+        self.map.start_pos
+    }
+
+    fn get_spawn_regions(&self) -> Vec<Vec<usize>> {
+        room_spawn_regions(&self.map)
+    }
+}
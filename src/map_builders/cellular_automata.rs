@@ -0,0 +1,75 @@
+use super::common::{SnapshotHistory, apply_boundary_walls, chunked_spawn_regions, place_start_and_stairs};
+use super::MapBuilder;
+use super::super::{Map, TileType};
+use rltk::RandomNumberGenerator;
+
+/// Percentage of tiles that start out as floor.
+const INITIAL_FLOOR_CHANCE: i32 = 55;
+const ITERATIONS: i32 = 15;
+
+/// Starts from noise and smooths it: a tile becomes wall when most of its neighbours are
+/// walls, or when it has none at all. The result is a network of natural-looking caves.
+pub struct CellularAutomataBuilder {
+    map: Map,
+    history: SnapshotHistory,
+}
+
+impl CellularAutomataBuilder {
+    pub fn new(depth: i32, record_history: bool) -> Self {
+        CellularAutomataBuilder { map: Map::new(depth), history: SnapshotHistory::new(record_history) }
+    }
+
+    fn wall_neighbours(&self, x: i32, y: i32) -> usize {
+        let mut walls = 0;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if (dx, dy) != (0, 0) && self.map.tiles[self.map.xy_idx(x + dx, y + dy)] == TileType::Wall {
+                    walls += 1;
+                }
+            }
+        }
+        walls
+    }
+}
+
+impl MapBuilder for CellularAutomataBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        for tile in self.map.tiles.iter_mut() {
+            *tile = if rng.roll_dice(1, 100) <= INITIAL_FLOOR_CHANCE { TileType::Floor } else { TileType::Wall };
+        }
+        apply_boundary_walls(&mut self.map);
+        self.history.record(&self.map);
+
+        for _i in 0..ITERATIONS {
+            let mut new_tiles = self.map.tiles.clone();
+            for y in 1..self.map.height - 1 {
+                for x in 1..self.map.width - 1 {
+                    let walls = self.wall_neighbours(x, y);
+                    let idx = self.map.xy_idx(x, y);
+                    new_tiles[idx] = if walls > 4 || walls == 0 { TileType::Wall } else { TileType::Floor };
+                }
+            }
+            self.map.tiles = new_tiles;
+            self.history.record(&self.map);
+        }
+
+        place_start_and_stairs(&mut self.map);
+        self.history.record(&self.map);
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_snapshot_history(&self) -> Vec<Map> {
+        self.history.frames()
+    }
+
+    fn get_starting_position(&self) -> (i32, i32) {
+        self.map.start_pos
+    }
+
+    fn get_spawn_regions(&self) -> Vec<Vec<usize>> {
+        chunked_spawn_regions(&self.map)
+    }
+}
@@ -0,0 +1,207 @@
+use super::super::{Map, Rect, TileType};
+use std::cmp::{max, min};
+
+/// Side length of the square chunks that open maps are cut into for spawning.
+const SPAWN_REGION_SIZE: i32 = 16;
+
+pub fn apply_room_to_map(map: &mut Map, room: &Rect) {
+    for y in room.y1 + 1..=room.y2 {
+        for x in room.x1 + 1..=room.x2 {
+            let idx = map.xy_idx(x, y);
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
+
+pub fn apply_horizontal_tunnel(map: &mut Map, x1: i32, x2: i32, y: i32) {
+    for x in min(x1, x2)..=max(x1, x2) {
+        dig_inside_boundary(map, x, y);
+    }
+}
+
+pub fn apply_vertical_tunnel(map: &mut Map, y1: i32, y2: i32, x: i32) {
+    for y in min(y1, y2)..=max(y1, y2) {
+        dig_inside_boundary(map, x, y);
+    }
+}
+
+/// Tunnels stop at the outer wall rather than wrapping onto the next row or running off the map.
+fn dig_inside_boundary(map: &mut Map, x: i32, y: i32) {
+    if x > 0 && x < map.width - 1 && y > 0 && y < map.height - 1 {
+        let idx = map.xy_idx(x, y);
+        map.tiles[idx] = TileType::Floor;
+    }
+}
+
+/// Floor tiles inside each room, skipping the first room where the player starts. Only floor
+/// counts, so anything culled as unreachable is never spawned on.
+pub fn room_spawn_regions(map: &Map) -> Vec<Vec<usize>> {
+    map.rooms
+        .iter()
+        .skip(1)
+        .map(|room| {
+            let mut region = Vec::new();
+            for y in room.y1 + 1..=room.y2 {
+                for x in room.x1 + 1..=room.x2 {
+                    let idx = map.xy_idx(x, y);
+                    if map.tiles[idx] == TileType::Floor {
+                        region.push(idx);
+                    }
+                }
+            }
+            region
+        })
+        .filter(|region| !region.is_empty())
+        .collect()
+}
+
+/// Maps without rooms are cut into square chunks of floor instead. The chunk holding the
+/// start is left empty so nothing spawns on top of the player.
+pub fn chunked_spawn_regions(map: &Map) -> Vec<Vec<usize>> {
+    let (start_x, start_y) = map.start_pos;
+    let mut regions = Vec::new();
+
+    for chunk_y in (0..map.height).step_by(SPAWN_REGION_SIZE as usize) {
+        for chunk_x in (0..map.width).step_by(SPAWN_REGION_SIZE as usize) {
+            let holds_start = start_x / SPAWN_REGION_SIZE == chunk_x / SPAWN_REGION_SIZE
+                && start_y / SPAWN_REGION_SIZE == chunk_y / SPAWN_REGION_SIZE;
+            if holds_start {
+                continue;
+            }
+
+            let mut region = Vec::new();
+            for y in chunk_y..min(chunk_y + SPAWN_REGION_SIZE, map.height) {
+                for x in chunk_x..min(chunk_x + SPAWN_REGION_SIZE, map.width) {
+                    let idx = map.xy_idx(x, y);
+                    if map.tiles[idx] == TileType::Floor {
+                        region.push(idx);
+                    }
+                }
+            }
+            if !region.is_empty() {
+                regions.push(region);
+            }
+        }
+    }
+
+    regions
+}
+
+/// The connectivity pass every builder finishes with. Floods out from `start_pos`, walls off
+/// any floor the player could never reach and returns the reachable tile that is farthest
+/// away, which is where open maps put their exit.
+pub fn cull_unreachable(map: &mut Map) -> usize {
+    let start_idx = map.xy_idx(map.start_pos.0, map.start_pos.1);
+    map.populate_blocked();
+    // No walkable path can be longer than the map has tiles, so nothing reachable is cut short
+    let max_depth = map.tiles.len() as f32;
+    let dijkstra_map = rltk::DijkstraMap::new(map.width, map.height, &[start_idx], map, max_depth);
+
+    let mut exit_tile = (start_idx, 0.0f32);
+    for (i, tile) in map.tiles.iter_mut().enumerate() {
+        if *tile == TileType::Floor {
+            let distance = dijkstra_map.map[i];
+            if distance == f32::MAX {
+                *tile = TileType::Wall;
+            } else if distance > exit_tile.1 {
+                exit_tile = (i, distance);
+            }
+        }
+    }
+
+    exit_tile.0
+}
+
+/// Walls in the outer edge so nothing a builder carves can leave the map.
+pub fn apply_boundary_walls(map: &mut Map) {
+    for x in 0..map.width {
+        let top = map.xy_idx(x, 0);
+        let bottom = map.xy_idx(x, map.height - 1);
+        map.tiles[top] = TileType::Wall;
+        map.tiles[bottom] = TileType::Wall;
+    }
+    for y in 0..map.height {
+        let left = map.xy_idx(0, y);
+        let right = map.xy_idx(map.width - 1, y);
+        map.tiles[left] = TileType::Wall;
+        map.tiles[right] = TileType::Wall;
+    }
+}
+
+/// For open maps: start on the floor nearest the middle, and put the stairs as far from there
+/// as the player can walk.
+pub fn place_start_and_stairs(map: &mut Map) {
+    let (centre_x, centre_y) = (map.width / 2, map.height / 2);
+    let width = map.width;
+    let nearest_floor = map.tiles.iter().enumerate()
+        .filter(|(_idx, tile)| **tile == TileType::Floor)
+        .map(|(idx, _tile)| idx as i32)
+        .min_by_key(|idx| {
+            let (dx, dy) = (idx % width - centre_x, idx / width - centre_y);
+            (dx * dx + dy * dy, *idx)
+        });
+
+    let start_idx = match nearest_floor {
+        Some(idx) => idx as usize,
+        None => {
+            // A builder that carved nothing at all still needs somewhere to stand
+            let idx = map.xy_idx(centre_x, centre_y);
+            map.tiles[idx] = TileType::Floor;
+            idx
+        }
+    };
+    map.start_pos = (start_idx as i32 % width, start_idx as i32 / width);
+
+    let exit_idx = cull_unreachable(map);
+    map.tiles[exit_idx] = TileType::DownStairs;
+}
+
+/// Copies of the map taken while it is being carved, so the generation can be replayed or
+/// dumped. Recording is off unless asked for, since some builders take hundreds of steps.
+pub struct SnapshotHistory {
+    enabled: bool,
+    frames: Vec<Map>,
+}
+
+impl SnapshotHistory {
+    pub fn new(enabled: bool) -> Self {
+        SnapshotHistory { enabled, frames: Vec::new() }
+    }
+
+    pub fn record(&mut self, map: &Map) {
+        if !self.enabled {
+            return;
+        }
+        // Snapshots are shown whole, not through the player's eyes
+        let mut snapshot = map.clone();
+        snapshot.revealed_tiles.iter_mut().for_each(|tile| *tile = true);
+        snapshot.visible_tiles.iter_mut().for_each(|tile| *tile = true);
+        self.frames.push(snapshot);
+    }
+
+    pub fn frames(&self) -> Vec<Map> {
+        self.frames.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_is_found_right_of_centre_without_carving() {
+        // A lone corridor entirely to the right of the middle, where a leftward search finds nothing
+        let mut map = Map::new(1);
+        let (x1, x2, y) = (map.width / 2 + 5, map.width - 10, map.height / 2 + 3);
+        apply_horizontal_tunnel(&mut map, x1, x2, y);
+        let floor_before = map.tiles.iter().filter(|t| **t == TileType::Floor).count();
+
+        place_start_and_stairs(&mut map);
+
+        assert_eq!(map.start_pos, (x1, y));
+        let walkable = map.tiles.iter().filter(|t| **t != TileType::Wall).count();
+        assert_eq!(walkable, floor_before);
+        let stairs = map.tiles.iter().position(|t| *t == TileType::DownStairs).unwrap();
+        assert_eq!(stairs, map.xy_idx(x2, y));
+    }
+}
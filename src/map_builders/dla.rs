@@ -0,0 +1,85 @@
+use super::common::{SnapshotHistory, chunked_spawn_regions, place_start_and_stairs};
+use super::MapBuilder;
+use super::super::{Map, TileType};
+use rltk::{Point, RandomNumberGenerator};
+
+/// Stop once this fraction of the map has been dug out.
+const FLOOR_PERCENT: f32 = 0.25;
+/// Particles fired between snapshots.
+const PARTICLES_PER_SNAPSHOT: usize = 20;
+
+/// Diffusion-limited aggregation: particles are fired from random points towards the centre
+/// and stick where they first touch open floor, growing a branching, coral-like cave.
+pub struct DlaBuilder {
+    map: Map,
+    history: SnapshotHistory,
+}
+
+impl DlaBuilder {
+    pub fn new(depth: i32, record_history: bool) -> Self {
+        DlaBuilder { map: Map::new(depth), history: SnapshotHistory::new(record_history) }
+    }
+
+    fn floor_count(&self) -> usize {
+        self.map.tiles.iter().filter(|tile| **tile == TileType::Floor).count()
+    }
+}
+
+impl MapBuilder for DlaBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        let centre = Point::new(self.map.width / 2, self.map.height / 2);
+
+        // A small seed for the particles to stick to
+        for y in centre.y - 1..=centre.y + 1 {
+            for x in centre.x - 1..=centre.x + 1 {
+                let idx = self.map.xy_idx(x, y);
+                self.map.tiles[idx] = TileType::Floor;
+            }
+        }
+
+        self.history.record(&self.map);
+
+        let desired_floor = (self.map.tiles.len() as f32 * FLOOR_PERCENT) as usize;
+        let mut particles = 0;
+        while self.floor_count() < desired_floor {
+            let origin = Point::new(rng.range(1, self.map.width - 1), rng.range(1, self.map.height - 1));
+            if self.map.tiles[self.map.xy_idx(origin.x, origin.y)] == TileType::Floor {
+                continue;
+            }
+
+            let mut previous = origin;
+            for step in rltk::line2d(rltk::LineAlg::Bresenham, origin, centre) {
+                if self.map.tiles[self.map.xy_idx(step.x, step.y)] == TileType::Floor {
+                    let idx = self.map.xy_idx(previous.x, previous.y);
+                    self.map.tiles[idx] = TileType::Floor;
+                    break;
+                }
+                previous = step;
+            }
+
+            particles += 1;
+            if particles % PARTICLES_PER_SNAPSHOT == 0 {
+                self.history.record(&self.map);
+            }
+        }
+
+        place_start_and_stairs(&mut self.map);
+        self.history.record(&self.map);
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_snapshot_history(&self) -> Vec<Map> {
+        self.history.frames()
+    }
+
+    fn get_starting_position(&self) -> (i32, i32) {
+        self.map.start_pos
+    }
+
+    fn get_spawn_regions(&self) -> Vec<Vec<usize>> {
+        chunked_spawn_regions(&self.map)
+    }
+}
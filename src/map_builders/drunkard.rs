@@ -0,0 +1,83 @@
+use super::common::{SnapshotHistory, chunked_spawn_regions, place_start_and_stairs};
+use super::MapBuilder;
+use super::super::{Map, TileType};
+use rltk::RandomNumberGenerator;
+
+/// Steps each digger takes before it gives up.
+const DRUNK_LIFETIME: i32 = 400;
+/// Stop once this fraction of the map has been dug out.
+const FLOOR_PERCENT: f32 = 0.5;
+
+/// Sends diggers stumbling about at random, clearing whatever they walk through. The first
+/// starts at the centre and the rest start from somewhere already dug, so it stays connected.
+pub struct DrunkardsWalkBuilder {
+    map: Map,
+    history: SnapshotHistory,
+}
+
+impl DrunkardsWalkBuilder {
+    pub fn new(depth: i32, record_history: bool) -> Self {
+        DrunkardsWalkBuilder { map: Map::new(depth), history: SnapshotHistory::new(record_history) }
+    }
+
+    fn floor_count(&self) -> usize {
+        self.map.tiles.iter().filter(|tile| **tile == TileType::Floor).count()
+    }
+
+    fn random_floor(&self, rng: &mut RandomNumberGenerator) -> (i32, i32) {
+        let floor: Vec<usize> = self.map.tiles.iter().enumerate()
+            .filter(|(_, tile)| **tile == TileType::Floor)
+            .map(|(idx, _)| idx)
+            .collect();
+        let idx = floor[rng.range(0, floor.len() as i32) as usize] as i32;
+        (idx % self.map.width, idx / self.map.width)
+    }
+}
+
+impl MapBuilder for DrunkardsWalkBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        let centre = (self.map.width / 2, self.map.height / 2);
+        let centre_idx = self.map.xy_idx(centre.0, centre.1);
+        self.map.tiles[centre_idx] = TileType::Floor;
+
+        let desired_floor = (self.map.tiles.len() as f32 * FLOOR_PERCENT) as usize;
+        let mut first_digger = true;
+
+        while self.floor_count() < desired_floor {
+            let (mut x, mut y) = if first_digger { centre } else { self.random_floor(rng) };
+            first_digger = false;
+
+            for _step in 0..DRUNK_LIFETIME {
+                let idx = self.map.xy_idx(x, y);
+                self.map.tiles[idx] = TileType::Floor;
+
+                match rng.roll_dice(1, 4) {
+                    1 => if x > 2 { x -= 1 },
+                    2 => if x < self.map.width - 2 { x += 1 },
+                    3 => if y > 2 { y -= 1 },
+                    _ => if y < self.map.height - 2 { y += 1 },
+                }
+            }
+            self.history.record(&self.map);
+        }
+
+        place_start_and_stairs(&mut self.map);
+        self.history.record(&self.map);
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_snapshot_history(&self) -> Vec<Map> {
+        self.history.frames()
+    }
+
+    fn get_starting_position(&self) -> (i32, i32) {
+        self.map.start_pos
+    }
+
+    fn get_spawn_regions(&self) -> Vec<Vec<usize>> {
+        chunked_spawn_regions(&self.map)
+    }
+}
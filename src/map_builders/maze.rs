@@ -0,0 +1,93 @@
+use super::common::{SnapshotHistory, chunked_spawn_regions, cull_unreachable};
+use super::MapBuilder;
+use super::super::{Map, TileType};
+use rltk::RandomNumberGenerator;
+
+/// Cells opened between snapshots; one frame per cell would make for a very long replay.
+const CELLS_PER_SNAPSHOT: usize = 20;
+
+/// A perfect maze carved with a recursive backtracker. Cells sit on odd tiles with the walls
+/// between them on even ones, so every passage is one tile wide.
+pub struct MazeBuilder {
+    map: Map,
+    history: SnapshotHistory,
+}
+
+impl MazeBuilder {
+    pub fn new(depth: i32, record_history: bool) -> Self {
+        MazeBuilder { map: Map::new(depth), history: SnapshotHistory::new(record_history) }
+    }
+
+    fn cell_to_tile(cell: (i32, i32)) -> (i32, i32) {
+        (cell.0 * 2 + 1, cell.1 * 2 + 1)
+    }
+}
+
+impl MapBuilder for MazeBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        let cells_wide = (self.map.width - 1) / 2;
+        let cells_high = (self.map.height - 1) / 2;
+        let mut visited = vec![false; (cells_wide * cells_high) as usize];
+        let cell_idx = |cell: (i32, i32)| (cell.1 * cells_wide + cell.0) as usize;
+
+        let mut opened = 0;
+        let mut stack = vec![(0, 0)];
+        visited[0] = true;
+        let start_tile = Self::cell_to_tile((0, 0));
+        let start_idx = self.map.xy_idx(start_tile.0, start_tile.1);
+        self.map.tiles[start_idx] = TileType::Floor;
+
+        while let Some(&current) = stack.last() {
+            let neighbours: Vec<(i32, i32)> = [(0, -1), (1, 0), (0, 1), (-1, 0)]
+                .iter()
+                .map(|(dx, dy)| (current.0 + dx, current.1 + dy))
+                .filter(|(x, y)| *x >= 0 && *x < cells_wide && *y >= 0 && *y < cells_high)
+                .filter(|cell| !visited[cell_idx(*cell)])
+                .collect();
+
+            if neighbours.is_empty() {
+                stack.pop();
+                continue;
+            }
+
+            let next = neighbours[rng.range(0, neighbours.len() as i32) as usize];
+            visited[cell_idx(next)] = true;
+
+            // Knock through the wall between the two cells as well as opening the new one
+            let (from_x, from_y) = Self::cell_to_tile(current);
+            let (to_x, to_y) = Self::cell_to_tile(next);
+            let wall_idx = self.map.xy_idx((from_x + to_x) / 2, (from_y + to_y) / 2);
+            let next_idx = self.map.xy_idx(to_x, to_y);
+            self.map.tiles[wall_idx] = TileType::Floor;
+            self.map.tiles[next_idx] = TileType::Floor;
+
+            stack.push(next);
+
+            opened += 1;
+            if opened % CELLS_PER_SNAPSHOT == 0 {
+                self.history.record(&self.map);
+            }
+        }
+
+        self.map.start_pos = start_tile;
+        let exit_idx = cull_unreachable(&mut self.map);
+        self.map.tiles[exit_idx] = TileType::DownStairs;
+        self.history.record(&self.map);
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_snapshot_history(&self) -> Vec<Map> {
+        self.history.frames()
+    }
+
+    fn get_starting_position(&self) -> (i32, i32) {
+        self.map.start_pos
+    }
+
+    fn get_spawn_regions(&self) -> Vec<Vec<usize>> {
+        chunked_spawn_regions(&self.map)
+    }
+}
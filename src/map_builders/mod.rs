@@ -0,0 +1,228 @@
+use super::{Map, TileType};
+use rltk::RandomNumberGenerator;
+
+mod common;
+mod simple_map;
+mod bsp_dungeon;
+mod cellular_automata;
+mod drunkard;
+mod maze;
+mod dla;
+
+use simple_map::SimpleMapBuilder;
+use bsp_dungeon::BspDungeonBuilder;
+use cellular_automata::CellularAutomataBuilder;
+use drunkard::DrunkardsWalkBuilder;
+use maze::MazeBuilder;
+use dla::DlaBuilder;
+
+/// A level generator. Call `build_map` once, then read the results back out.
+pub trait MapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator);
+    fn get_map(&self) -> Map;
+    /// Snapshots of the map after each step, oldest first. Empty unless recording was asked for.
+    fn get_snapshot_history(&self) -> Vec<Map>;
+    fn get_starting_position(&self) -> (i32, i32);
+    /// Groups of floor tile indices, each of which gets its own batch of spawns.
+    fn get_spawn_regions(&self) -> Vec<Vec<usize>>;
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum MapBuilderKind {
+    RoomsAndCorridors,
+    Bsp,
+    Caves,
+    DrunkardsWalk,
+    Maze,
+    Dla,
+}
+
+impl MapBuilderKind {
+    pub const ALL: [MapBuilderKind; 6] = [
+        MapBuilderKind::RoomsAndCorridors,
+        MapBuilderKind::Bsp,
+        MapBuilderKind::Caves,
+        MapBuilderKind::DrunkardsWalk,
+        MapBuilderKind::Maze,
+        MapBuilderKind::Dla,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MapBuilderKind::RoomsAndCorridors => "Rooms and corridors",
+            MapBuilderKind::Bsp => "BSP rooms",
+            MapBuilderKind::Caves => "Caves",
+            MapBuilderKind::DrunkardsWalk => "Drunkard's walk",
+            MapBuilderKind::Maze => "Maze",
+            MapBuilderKind::Dla => "Diffusion-limited aggregation",
+        }
+    }
+
+    /// Short name used on the command line.
+    pub fn arg_name(self) -> &'static str {
+        match self {
+            MapBuilderKind::RoomsAndCorridors => "rooms",
+            MapBuilderKind::Bsp => "bsp",
+            MapBuilderKind::Caves => "caves",
+            MapBuilderKind::DrunkardsWalk => "drunkard",
+            MapBuilderKind::Maze => "maze",
+            MapBuilderKind::Dla => "dla",
+        }
+    }
+
+    pub fn from_arg_name(name: &str) -> Option<MapBuilderKind> {
+        MapBuilderKind::ALL.iter().copied().find(|kind| kind.arg_name() == name)
+    }
+}
+
+pub fn builder_for(kind: MapBuilderKind, depth: i32, record_history: bool) -> Box<dyn MapBuilder> {
+    match kind {
+        MapBuilderKind::RoomsAndCorridors => Box::new(SimpleMapBuilder::new(depth, record_history)),
+        MapBuilderKind::Bsp => Box::new(BspDungeonBuilder::new(depth, record_history)),
+        MapBuilderKind::Caves => Box::new(CellularAutomataBuilder::new(depth, record_history)),
+        MapBuilderKind::DrunkardsWalk => Box::new(DrunkardsWalkBuilder::new(depth, record_history)),
+        MapBuilderKind::Maze => Box::new(MazeBuilder::new(depth, record_history)),
+        MapBuilderKind::Dla => Box::new(DlaBuilder::new(depth, record_history)),
+    }
+}
+
+pub fn random_builder(rng: &mut RandomNumberGenerator, depth: i32, record_history: bool) -> Box<dyn MapBuilder> {
+    let roll = rng.range(0, MapBuilderKind::ALL.len() as i32);
+    builder_for(MapBuilderKind::ALL[roll as usize], depth, record_history)
+}
+
+/// Renders a map as plain text, one line per row, using the same glyphs as the game.
+pub fn map_to_text(map: &Map) -> String {
+    let mut text = String::with_capacity(map.tiles.len() + map.height as usize);
+    for row in map.tiles.chunks(map.width as usize) {
+        for tile in row {
+            text.push(match tile {
+                TileType::Wall => '#',
+                TileType::Floor => '.',
+                TileType::DownStairs => '>',
+            });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Every snapshot as a numbered text frame. With a fixed seed the output is stable, so it can
+/// be diffed against a stored copy to catch unintended changes to a generator.
+pub fn history_to_text(history: &[Map]) -> String {
+    let mut text = String::new();
+    for (i, frame) in history.iter().enumerate() {
+        text.push_str(&format!("--- frame {}/{} ---\n", i + 1, history.len()));
+        text.push_str(&map_to_text(frame));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{MAPHEIGHT, MAPWIDTH};
+
+    const SEEDS: u64 = 2000;
+
+    /// Over thousands of seeds, `kind` leaves nothing the player needs out of reach: not the
+    /// start, not the stairs, and not any tile a monster or item can spawn on.
+    fn assert_everything_reachable(kind: MapBuilderKind) {
+        for seed in 0..SEEDS {
+            let mut rng = RandomNumberGenerator::seeded(seed);
+            let mut builder = builder_for(kind, 1, false);
+            builder.build_map(&mut rng);
+            let mut map = builder.get_map();
+            let context = format!("{} with seed {}", kind.name(), seed);
+
+            let walkable_before = map.tiles.iter().filter(|t| **t != TileType::Wall).count();
+            common::cull_unreachable(&mut map);
+            let walkable_after = map.tiles.iter().filter(|t| **t != TileType::Wall).count();
+            assert_eq!(walkable_before, walkable_after, "{} left unreachable floor", context);
+
+            let start_idx = map.xy_idx(map.start_pos.0, map.start_pos.1);
+            assert_eq!(builder.get_starting_position(), map.start_pos, "{}", context);
+            assert!(map.tiles[start_idx] != TileType::Wall, "{} starts in a wall", context);
+
+            map.populate_blocked();
+            let dijkstra_map = rltk::DijkstraMap::new(map.width, map.height, &[start_idx], &map, map.tiles.len() as f32);
+            let reachable = |idx: usize| dijkstra_map.map[idx] < f32::MAX;
+
+            let stairs: Vec<usize> = (0..map.tiles.len()).filter(|idx| map.tiles[*idx] == TileType::DownStairs).collect();
+            assert_eq!(stairs.len(), 1, "{} should have exactly one way down", context);
+            assert!(reachable(stairs[0]), "{} has unreachable stairs", context);
+            assert_ne!(stairs[0], start_idx, "{} puts the stairs on the start", context);
+
+            let regions = builder.get_spawn_regions();
+            assert!(!regions.is_empty(), "{} has nowhere to spawn anything", context);
+            for idx in regions.iter().flatten() {
+                assert!(reachable(*idx), "{} can spawn at unreachable tile {}", context, idx);
+            }
+        }
+    }
+
+    #[test]
+    fn rooms_and_corridors_maps_are_fully_reachable() {
+        assert_everything_reachable(MapBuilderKind::RoomsAndCorridors);
+    }
+
+    #[test]
+    fn bsp_maps_are_fully_reachable() {
+        assert_everything_reachable(MapBuilderKind::Bsp);
+    }
+
+    #[test]
+    fn caves_maps_are_fully_reachable() {
+        assert_everything_reachable(MapBuilderKind::Caves);
+    }
+
+    #[test]
+    fn drunkards_walk_maps_are_fully_reachable() {
+        assert_everything_reachable(MapBuilderKind::DrunkardsWalk);
+    }
+
+    #[test]
+    fn maze_maps_are_fully_reachable() {
+        assert_everything_reachable(MapBuilderKind::Maze);
+    }
+
+    #[test]
+    fn dla_maps_are_fully_reachable() {
+        assert_everything_reachable(MapBuilderKind::Dla);
+    }
+
+    fn dump(kind: MapBuilderKind, seed: u64) -> (usize, String) {
+        let mut builder = builder_for(kind, 1, true);
+        builder.build_map(&mut RandomNumberGenerator::seeded(seed));
+        let history = builder.get_snapshot_history();
+        (history.len(), history_to_text(&history))
+    }
+
+    #[test]
+    fn generation_dump_is_deterministic_and_well_formed() {
+        for kind in [MapBuilderKind::RoomsAndCorridors, MapBuilderKind::Maze] {
+            let (frames, text) = dump(kind, 7);
+            assert!(frames > 1, "{} recorded no history", kind.name());
+            assert_eq!(text, dump(kind, 7).1, "{} dump changed between runs", kind.name());
+
+            let lines: Vec<&str> = text.lines().collect();
+            assert_eq!(lines.len(), frames * (MAPHEIGHT + 1));
+            for (i, frame) in lines.chunks(MAPHEIGHT + 1).enumerate() {
+                assert_eq!(frame[0], format!("--- frame {}/{} ---", i + 1, frames));
+                for row in &frame[1..] {
+                    assert_eq!(row.chars().count(), MAPWIDTH);
+                    assert!(row.chars().all(|c| matches!(c, '#' | '.' | '>')), "unexpected glyph in {:?}", row);
+                }
+            }
+
+            // The last frame is the finished map, stairs and all
+            assert_eq!(lines[lines.len() - MAPHEIGHT..].join("\n") + "\n", map_to_text(&final_map(kind, 7)));
+        }
+    }
+
+    fn final_map(kind: MapBuilderKind, seed: u64) -> Map {
+        let mut builder = builder_for(kind, 1, false);
+        builder.build_map(&mut RandomNumberGenerator::seeded(seed));
+        builder.get_map()
+    }
+}
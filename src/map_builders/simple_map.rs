@@ -0,0 +1,82 @@
+use super::common::{SnapshotHistory, cull_unreachable, apply_horizontal_tunnel, apply_room_to_map, apply_vertical_tunnel, room_spawn_regions};
+use super::MapBuilder;
+use super::super::{Map, Rect, TileType};
+use rltk::RandomNumberGenerator;
+
+const MAX_ROOMS: i32 = 30;
+const MIN_SIZE: i32 = 6;
+const MAX_SIZE: i32 = 10;
+
+/// Rectangular rooms scattered at random, each joined to the previous one by an L-shaped tunnel.
+pub struct SimpleMapBuilder {
+    map: Map,
+    history: SnapshotHistory,
+}
+
+impl SimpleMapBuilder {
+    pub fn new(depth: i32, record_history: bool) -> Self {
+        SimpleMapBuilder { map: Map::new(depth), history: SnapshotHistory::new(record_history) }
+    }
+}
+
+impl MapBuilder for SimpleMapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        let map = &mut self.map;
+
+        for _i in 0..MAX_ROOMS {
+            let w = rng.range(MIN_SIZE, MAX_SIZE);
+            let h = rng.range(MIN_SIZE, MAX_SIZE);
+            let x = rng.roll_dice(1, map.width - w - 1) - 1;
+            let y = rng.roll_dice(1, map.height - h - 1) - 1;
+            let new_room = Rect::new(x, y, w, h);
+            let mut ok = true;
+            for other_room in map.rooms.iter() {
+                if new_room.intersect(other_room) {
+                    ok = false
+                }
+            }
+            if ok {
+                apply_room_to_map(map, &new_room);
+
+                if map.rooms.is_empty() {
+                    map.start_pos = new_room.center();
+                } else {
+                    let (new_x, new_y) = new_room.center();
+                    let (prev_x, prev_y) = map.rooms[map.rooms.len() - 1].center();
+                    if rng.range(0, 2) == 1 {
+                        apply_horizontal_tunnel(map, prev_x, new_x, prev_y);
+                        apply_vertical_tunnel(map, prev_y, new_y, new_x);
+                    } else {
+                        apply_vertical_tunnel(map, prev_y, new_y, prev_x);
+                        apply_horizontal_tunnel(map, prev_x, new_x, new_y);
+                    }
+                }
+
+                map.rooms.push(new_room);
+                self.history.record(map);
+            }
+        }
+
+        cull_unreachable(map);
+        let (stairs_x, stairs_y) = map.rooms[map.rooms.len() - 1].center();
+        let stairs_idx = map.xy_idx(stairs_x, stairs_y);
+        map.tiles[stairs_idx] = TileType::DownStairs;
+        self.history.record(map);
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_snapshot_history(&self) -> Vec<Map> {
+        self.history.frames()
+    }
+
+    fn get_starting_position(&self) -> (i32, i32) {
+        self.map.start_pos
+    }
+
+    fn get_spawn_regions(&self) -> Vec<Vec<usize>> {
+        room_spawn_regions(&self.map)
+    }
+}
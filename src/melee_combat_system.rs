@@ -1,33 +1,96 @@
-use rltk::console;
+use rltk::{DiceType, RandomNumberGenerator, RGB};
 use specs::prelude::*;
-use super::{CombatStats, WantsToMelee, Name, SufferDamage};
+use super::combat::{self, Attack, AttackResult};
+use super::{Attributes, CombatStats, DamageDice, DefenceBonus, EquipmentSlot, Equipped, GameLog, LogCategory, MeleePowerBonus,
+    Name, RunStats, Skills, SufferDamage, WantsToMelee};
+
+/// Fists, claws and anything else without dice of its own.
+const UNARMED: DiceType = DiceType { n_dice: 1, die_type: 4, bonus: 0 };
 
 pub struct MeleeCombatSystem {}
 
 impl<'a> System<'a> for MeleeCombatSystem {
+    #[allow(clippy::type_complexity)]
     type SystemData = ( Entities<'a>,
                         WriteStorage<'a, WantsToMelee>,
                         ReadStorage<'a, Name>,
                         ReadStorage<'a, CombatStats>,
-                        WriteStorage<'a, SufferDamage>
+                        WriteStorage<'a, SufferDamage>,
+                        ReadExpect<'a, Entity>,
+                        WriteExpect<'a, RunStats>,
+                        WriteExpect<'a, GameLog>,
+                        ReadStorage<'a, MeleePowerBonus>,
+                        ReadStorage<'a, DefenceBonus>,
+                        ReadStorage<'a, Equipped>,
+                        ReadStorage<'a, Attributes>,
+                        ReadStorage<'a, Skills>,
+                        ReadStorage<'a, DamageDice>,
+                        WriteExpect<'a, RandomNumberGenerator>
                       );
 
     fn run(&mut self, data : Self::SystemData) {
-        let (entities, mut wants_melee, names, combat_stats, mut inflict_damage) = data;
+        let (entities, mut wants_melee, names, combat_stats, mut inflict_damage, player_entity, mut run_stats, mut log,
+            melee_power_bonuses, defence_bonuses, equipped, attributes, skills, damage_dice, mut rng) = data;
+
+        let default_attributes = Attributes::default();
+        let default_skills = Skills::default();
 
-        for (_entity, wants_melee, name, stats) in (&entities, &wants_melee, &names, &combat_stats).join() {
+        for (entity, wants_melee, name, stats) in (&entities, &wants_melee, &names, &combat_stats).join() {
             if stats.current_hp > 0 {
-                let target_stats = combat_stats.get(wants_melee.target).unwrap();
-                if target_stats.current_hp > 0 {
+                let target_stats = combat_stats.get(wants_melee.target);
+                if let Some(target_stats) = target_stats.filter(|t| t.current_hp > 0) {
                     let target_name = names.get(wants_melee.target).unwrap();
 
-                    let damage = i32::max(0, stats.power - target_stats.defence);
+                    let power_bonus: i32 = (&melee_power_bonuses, &equipped).join()
+                        .filter(|(_bonus, equipped_by)| equipped_by.owner == entity)
+                        .map(|(bonus, _equipped_by)| bonus.power)
+                        .sum();
+                    let defence_bonus: i32 = (&defence_bonuses, &equipped).join()
+                        .filter(|(_bonus, equipped_by)| equipped_by.owner == wants_melee.target)
+                        .map(|(bonus, _equipped_by)| bonus.defence)
+                        .sum();
+
+                    // A wielded weapon replaces the attacker's own natural attack
+                    let weapon_dice = (&damage_dice, &equipped).join()
+                        .find(|(_dice, equipped_by)| equipped_by.owner == entity && equipped_by.slot == EquipmentSlot::Melee)
+                        .map(|(dice, _equipped_by)| dice.dice);
+                    let dice = weapon_dice.or_else(|| damage_dice.get(entity).map(|d| d.dice)).unwrap_or(UNARMED);
+
+                    let attacker_attributes = attributes.get(entity).unwrap_or(&default_attributes);
+                    let attacker_skills = skills.get(entity).unwrap_or(&default_skills);
+                    let might = combat::attribute_bonus(attacker_attributes.might);
+                    let attack = Attack {
+                        to_hit: might + attacker_skills.melee,
+                        damage_dice: dice,
+                        damage_bonus: might + stats.power + power_bonus,
+                    };
+
+                    let target_attributes = attributes.get(wants_melee.target).unwrap_or(&default_attributes);
+                    let target_skills = skills.get(wants_melee.target).unwrap_or(&default_skills);
+                    let armour_class = combat::armour_class(target_attributes.quickness, target_skills.defence,
+                        target_stats.defence + defence_bonus);
+
+                    let outcome = combat::resolve_attack(&mut rng, &attack, armour_class);
+
+                    // Blows landing on the player stand out from the player's own
+                    let colour = if wants_melee.target == *player_entity { RGB::named(rltk::RED) } else { RGB::named(rltk::WHITE) };
+
+                    let (message, damage) = match outcome.result {
+                        AttackResult::Fumble => (format!("{} swings wildly at {} and misses (rolled a 1).", name.name, target_name.name), 0),
+                        AttackResult::Miss => (format!("{} misses {} (rolled {} vs AC {}).", name.name, target_name.name,
+                            outcome.total_roll, outcome.armour_class), 0),
+                        AttackResult::Hit { damage } => (format!("{} hits {}, for {} hp (rolled {} vs AC {}).", name.name,
+                            target_name.name, damage, outcome.total_roll, outcome.armour_class), damage),
+                        AttackResult::Critical { damage } => (format!("{} lands a critical hit on {}, for {} hp (rolled a 20)!",
+                            name.name, target_name.name, damage), damage),
+                    };
+                    log.push(run_stats.turns, LogCategory::Combat, colour, message);
 
-                    if damage == 0 {
-                        console::log(&format!("{} is unable to hurt {}", &name.name, &target_name.name));
-                    } else {
-                        console::log(&format!("{} hits {}, for {} hp.", &name.name, &target_name.name, damage));
-                        SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, damage);
+                    if damage > 0 {
+                        SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, damage, entity);
+                        if wants_melee.target == *player_entity {
+                            run_stats.last_attacker = Some(name.name.clone());
+                        }
                     }
                 }
             }
@@ -1,5 +1,5 @@
-use super::{Map, Monster, Name, Position, Viewshed};
-use rltk::{console, Point};
+use super::{Confusion, Map, Monster, Position, Viewshed, WantsToMelee};
+use rltk::{Point, RandomNumberGenerator};
 use specs::prelude::*;
 
 pub struct MonsterAI {}
@@ -10,37 +10,69 @@ impl<'a> System<'a> for MonsterAI {
     WriteExpect<'a, Map>,
     WriteStorage<'a, Viewshed>,
     ReadExpect<'a, Point>,
+    ReadExpect<'a, Entity>,
+    Entities<'a>,
     ReadStorage<'a, Monster>,
-    ReadStorage<'a, Name>,
     WriteStorage<'a, Position>,
+    WriteStorage<'a, WantsToMelee>,
+    ReadStorage<'a, Confusion>,
+    WriteExpect<'a, RandomNumberGenerator>,
   );
 
   fn run(&mut self, data: Self::SystemData) {
-    let (mut map, mut viewshed, player_pos, monster, named, mut pos) = data;
+    let (mut map, mut viewshed, player_pos, player_entity, entities, monster, mut pos, mut wants_to_melee, confused, mut rng) = data;
 
-    for (mut viewshed, _monster, named, mut pos) in
-      (&mut viewshed, &monster, &named, &mut pos).join()
+    for (entity, viewshed, _monster, pos) in
+      (&entities, &mut viewshed, &monster, &mut pos).join()
     {
-      if viewshed.visible_tiles.contains(&*player_pos) {
-        let distance =
-          rltk::DistanceAlg::Pythagoras.distance2d(Point::new(pos.x, pos.y), *player_pos);
-        if distance < 1.5 {
-          // Attack goes here
-          console::log(&format!("{} shouts insults", named.name));
-          return;
+      // Confused monsters stagger about instead of acting; StatusEffectSystem counts the confusion down.
+      // The player doesn't block tiles, so their square has to be ruled out separately
+      if confused.get(entity).is_some() {
+        let (dx, dy) = (rng.range(-1, 2), rng.range(-1, 2));
+        let (new_x, new_y) = (pos.x + dx, pos.y + dy);
+        let onto_player = new_x == player_pos.x && new_y == player_pos.y;
+        if !onto_player && new_x > 0 && new_x < map.width - 1 && new_y > 0 && new_y < map.height - 1 {
+          let new_idx = map.xy_idx(new_x, new_y);
+          if !map.blocked[new_idx] {
+            let idx = map.xy_idx(pos.x, pos.y);
+            map.blocked[idx] = false;
+            map.blocked[new_idx] = true;
+            pos.x = new_x;
+            pos.y = new_y;
+            viewshed.dirty = true;
+          }
         }
+        continue;
+      }
 
-        let path = rltk::a_star_search(
-          map.xy_idx(pos.x, pos.y) as i32,
-          map.xy_idx(player_pos.x, player_pos.y) as i32,
-          &mut *map,
-        );
+      if !viewshed.visible_tiles.contains(&*player_pos) {
+        continue;
+      }
 
-        if path.success && path.steps.len() > 1 {
-          pos.x = path.steps[1] as i32 % map.width;
-          pos.y = path.steps[1] as i32 / map.width;
-          viewshed.dirty = true;
-        }
+      let distance =
+        rltk::DistanceAlg::Pythagoras.distance2d(Point::new(pos.x, pos.y), *player_pos);
+      if distance < 1.5 {
+        wants_to_melee
+          .insert(entity, WantsToMelee { target: *player_entity })
+          .expect("Unable to insert attack");
+        continue;
+      }
+
+      let path = rltk::a_star_search(
+        map.xy_idx(pos.x, pos.y) as i32,
+        map.xy_idx(player_pos.x, player_pos.y) as i32,
+        &*map,
+      );
+
+      if path.success && path.steps.len() > 1 {
+        // Keep the blocked list current so monsters later in the join don't step onto this one
+        let mut idx = map.xy_idx(pos.x, pos.y);
+        map.blocked[idx] = false;
+        pos.x = path.steps[1] as i32 % map.width;
+        pos.y = path.steps[1] as i32 / map.width;
+        idx = map.xy_idx(pos.x, pos.y);
+        map.blocked[idx] = true;
+        viewshed.dirty = true;
       }
     }
   }
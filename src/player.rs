@@ -1,7 +1,41 @@
-use rltk::{VirtualKeyCode, Rltk, Point};
+use rltk::{VirtualKeyCode, Rltk, Point, RGB};
 use specs::prelude::*;
-use super::{Position, Player, Map, State, Viewshed, RunState, CombatStats, WantsToMelee};
-use std::cmp::{min, max};
+use super::{Position, Player, Map, State, Viewshed, RunState, CombatStats, WantsToMelee, GameLog, LogCategory, RunStats,
+    Item, WantsToPickupItem, TileType, Monster, Name, ReceiveHealing};
+
+/// Resting heals 1 HP every this many turns.
+const REST_TURNS_PER_HP: i32 = 3;
+
+/// A command that plays out over many turns, one per tick, until it's finished or something
+/// needs the player's attention.
+#[derive(Clone, Debug)]
+pub enum AutoCommand {
+    /// Counts the turns rested so far.
+    Rest { turns: i32 },
+    Explore,
+    /// The tiles still to walk, nearest first.
+    Travel { path: Vec<usize> },
+}
+
+impl AutoCommand {
+    fn verb(&self) -> &'static str {
+        match self {
+            AutoCommand::Rest { .. } => "rest",
+            AutoCommand::Explore => "explore",
+            AutoCommand::Travel { .. } => "travel",
+        }
+    }
+}
+
+pub struct AutoAction {
+    command: AutoCommand,
+    /// How long the log was after the last step. Anything posted since then interrupts.
+    log_len: usize,
+    /// The player's HP after the last step. Losing any, even unseen, interrupts too.
+    hp: i32,
+    /// Monsters in view after the last step. Only one that wasn't interrupts.
+    seen_monsters: Vec<Entity>,
+}
 
 pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
     let mut positions = ecs.write_storage::<Position>();
@@ -27,8 +61,8 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
         }
 
         if !map.blocked[destination_idx] {
-            pos.x = min(79 , max(0, pos.x + delta_x));
-            pos.y = min(49, max(0, pos.y + delta_y));
+            pos.x = (pos.x + delta_x).clamp(0, map.width - 1);
+            pos.y = (pos.y + delta_y).clamp(0, map.height - 1);
             player_pos.x = pos.x;
             player_pos.y = pos.y;
             
@@ -37,10 +71,251 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
     }
 }
 
+fn get_item(ecs: &mut World) -> RunState {
+    let player_pos = ecs.fetch::<Point>();
+    let player_entity = ecs.fetch::<Entity>();
+    let entities = ecs.entities();
+    let items = ecs.read_storage::<Item>();
+    let positions = ecs.read_storage::<Position>();
+
+    let target_item = (&entities, &items, &positions)
+        .join()
+        .find(|(_entity, _item, pos)| pos.x == player_pos.x && pos.y == player_pos.y)
+        .map(|(entity, _item, _pos)| entity);
+
+    match target_item {
+        None => {
+            let turn = ecs.fetch::<RunStats>().turns;
+            ecs.write_resource::<GameLog>().push(turn, LogCategory::Item, RGB::named(rltk::GREY), "There is nothing here to pick up.");
+            RunState::AwaitingInput
+        }
+        Some(item) => {
+            let mut pickup = ecs.write_storage::<WantsToPickupItem>();
+            pickup.insert(*player_entity, WantsToPickupItem { collected_by: *player_entity, item }).expect("Unable to insert want to pickup");
+            RunState::PlayerTurn
+        }
+    }
+}
+
+fn try_next_level(ecs: &mut World) -> RunState {
+    let player_pos = ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    let idx = map.xy_idx(player_pos.x, player_pos.y);
+    if map.tiles[idx] == TileType::DownStairs {
+        return RunState::NextLevel;
+    }
+
+    let turn = ecs.fetch::<RunStats>().turns;
+    ecs.write_resource::<GameLog>().push(turn, LogCategory::System, RGB::named(rltk::GREY), "There is no way down from here.");
+    RunState::AwaitingInput
+}
+
+fn player_hp(ecs: &World) -> i32 {
+    let player_entity = *ecs.fetch::<Entity>();
+    ecs.read_storage::<CombatStats>().get(player_entity).map(|stats| stats.current_hp).unwrap_or(0)
+}
+
+/// Every monster in the player's sight.
+fn visible_monsters(ecs: &World) -> Vec<Entity> {
+    let map = ecs.fetch::<Map>();
+    let monsters = ecs.read_storage::<Monster>();
+
+    map.visible_tiles.iter().enumerate()
+        .filter(|(_idx, visible)| **visible)
+        .flat_map(|(idx, _visible)| map.tile_content[idx].iter())
+        .filter(|entity| monsters.get(**entity).is_some())
+        .copied()
+        .collect()
+}
+
+fn monster_name(ecs: &World, monster: Entity) -> String {
+    ecs.read_storage::<Name>().get(monster).map(|n| n.name.clone()).unwrap_or_else(|| "something".to_string())
+}
+
+fn start_auto_command(gs: &mut State, command: AutoCommand) -> RunState {
+    let turn = gs.ecs.fetch::<RunStats>().turns;
+    let seen_monsters = visible_monsters(&gs.ecs);
+
+    // Travelling away from a monster is fine; resting or wandering off next to one isn't
+    if !matches!(command, AutoCommand::Travel { .. }) {
+        if let Some(monster) = seen_monsters.first() {
+            let name = monster_name(&gs.ecs, *monster);
+            gs.ecs.write_resource::<GameLog>().push(turn, LogCategory::System, RGB::named(rltk::GREY),
+                format!("You can't {} with {} in view.", command.verb(), name));
+            return RunState::AwaitingInput;
+        }
+    }
+
+    let log_len = gs.ecs.fetch::<GameLog>().entries().len();
+    let hp = player_hp(&gs.ecs);
+    gs.auto_action = Some(AutoAction { command, log_len, hp, seen_monsters });
+    continue_auto_command(gs)
+}
+
+/// Takes the next step of the command in progress, or ends it if it's done or the player has
+/// something new to look at.
+fn continue_auto_command(gs: &mut State) -> RunState {
+    let mut action = match gs.auto_action.take() {
+        Some(action) => action,
+        None => return RunState::AwaitingInput,
+    };
+
+    let turn = gs.ecs.fetch::<RunStats>().turns;
+    if gs.ecs.fetch::<GameLog>().entries().len() != action.log_len {
+        return RunState::AwaitingInput;
+    }
+    if player_hp(&gs.ecs) < action.hp {
+        gs.ecs.write_resource::<GameLog>().push(turn, LogCategory::System, RGB::named(rltk::ORANGE),
+            "You are hurt, and stop what you were doing.");
+        return RunState::AwaitingInput;
+    }
+    let in_view = visible_monsters(&gs.ecs);
+    if let Some(monster) = in_view.iter().find(|monster| !action.seen_monsters.contains(monster)) {
+        let name = monster_name(&gs.ecs, *monster);
+        gs.ecs.write_resource::<GameLog>().push(turn, LogCategory::System, RGB::named(rltk::ORANGE),
+            format!("You spot {}!", name));
+        return RunState::AwaitingInput;
+    }
+    action.seen_monsters = in_view;
+
+    let stepped = match &mut action.command {
+        AutoCommand::Rest { turns } => rest_step(&mut gs.ecs, turns),
+        AutoCommand::Explore => explore_step(&mut gs.ecs),
+        AutoCommand::Travel { path } => travel_step(&mut gs.ecs, path),
+    };
+    if !stepped {
+        return RunState::AwaitingInput;
+    }
+
+    action.log_len = gs.ecs.fetch::<GameLog>().entries().len();
+    action.hp = player_hp(&gs.ecs);
+    gs.auto_action = Some(action);
+    RunState::PlayerTurn
+}
+
+/// Spends a turn resting, or returns false once the player is back to full health.
+fn rest_step(ecs: &mut World, turns: &mut i32) -> bool {
+    let player_entity = *ecs.fetch::<Entity>();
+    let (current_hp, max_hp) = match ecs.read_storage::<CombatStats>().get(player_entity) {
+        Some(stats) => (stats.current_hp, stats.max_hp),
+        None => return false,
+    };
+    if current_hp >= max_hp {
+        let turn = ecs.fetch::<RunStats>().turns;
+        ecs.write_resource::<GameLog>().push(turn, LogCategory::System, RGB::named(rltk::GREEN), "You feel rested.");
+        return false;
+    }
+
+    *turns += 1;
+    if *turns % REST_TURNS_PER_HP == 0 {
+        ReceiveHealing::new_healing(&mut ecs.write_storage::<ReceiveHealing>(), player_entity, 1);
+    }
+    true
+}
+
+/// Steps towards the nearest floor the player hasn't seen yet, or returns false if none can
+/// be reached.
+fn explore_step(ecs: &mut World) -> bool {
+    let step = {
+        let map = ecs.fetch::<Map>();
+        let player_pos = ecs.fetch::<Point>();
+        let player_idx = map.xy_idx(player_pos.x, player_pos.y);
+
+        let unexplored: Vec<usize> = (0..map.tiles.len())
+            .filter(|idx| !map.revealed_tiles[*idx] && map.tiles[*idx] != TileType::Wall)
+            .collect();
+        let dijkstra_map = rltk::DijkstraMap::new(map.width, map.height, &unexplored, &*map, map.tiles.len() as f32);
+
+        if dijkstra_map.map[player_idx] == f32::MAX {
+            None
+        } else {
+            rltk::DijkstraMap::find_lowest_exit(&dijkstra_map, player_idx, &*map)
+                .map(|idx| (idx as i32 % map.width - player_pos.x, idx as i32 / map.width - player_pos.y))
+        }
+    };
+
+    match step {
+        Some((delta_x, delta_y)) => {
+            try_move_player(delta_x, delta_y, ecs);
+            true
+        }
+        None => {
+            let turn = ecs.fetch::<RunStats>().turns;
+            ecs.write_resource::<GameLog>().push(turn, LogCategory::System, RGB::named(rltk::GREY),
+                "There's nowhere left to explore.");
+            false
+        }
+    }
+}
+
+/// Takes the next step along `path`, or returns false once there, or if the way is blocked.
+fn travel_step(ecs: &mut World, path: &mut Vec<usize>) -> bool {
+    if path.is_empty() {
+        return false;
+    }
+    let next = path.remove(0);
+
+    let (blocked, delta_x, delta_y) = {
+        let map = ecs.fetch::<Map>();
+        let player_pos = ecs.fetch::<Point>();
+        (map.blocked[next], next as i32 % map.width - player_pos.x, next as i32 / map.width - player_pos.y)
+    };
+    // Paths only avoid blockers the player could see when it was plotted
+    if blocked {
+        let turn = ecs.fetch::<RunStats>().turns;
+        ecs.write_resource::<GameLog>().push(turn, LogCategory::System, RGB::named(rltk::GREY),
+            "Something blocks your way, and you stop.");
+        return false;
+    }
+    // Something has moved the player off the path
+    if delta_x.abs() > 1 || delta_y.abs() > 1 {
+        return false;
+    }
+
+    try_move_player(delta_x, delta_y, ecs);
+    true
+}
+
+/// The path the player would travel to reach the tile under the mouse, if there's one to take.
+pub fn travel_path_to_mouse(ecs: &World, ctx: &Rltk) -> Option<Vec<usize>> {
+    let map = ecs.fetch::<Map>();
+    let player_pos = ecs.fetch::<Point>();
+    let (mouse_x, mouse_y) = ctx.mouse_pos();
+    if mouse_x < 1 || mouse_x >= map.width - 1 || mouse_y < 1 || mouse_y >= map.height - 1 {
+        return None;
+    }
+    if mouse_x == player_pos.x && mouse_y == player_pos.y {
+        return None;
+    }
+
+    let target = map.xy_idx(mouse_x, mouse_y);
+    if map.tiles[target] == TileType::Wall {
+        return None;
+    }
+    map.known_path(map.xy_idx(player_pos.x, player_pos.y), target)
+}
+
 pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
+    // Any key or click stops a command that's already underway
+    if gs.auto_action.is_some() {
+        if ctx.key.is_some() || ctx.left_click {
+            gs.auto_action = None;
+            return RunState::AwaitingInput;
+        }
+        return continue_auto_command(gs);
+    }
+
+    // Clicking a tile the player has seen walks them there
+    if ctx.left_click {
+        return match travel_path_to_mouse(&gs.ecs, ctx) {
+            Some(path) => start_auto_command(gs, AutoCommand::Travel { path: path[1..].to_vec() }),
+            None => RunState::AwaitingInput,
+        };
+    }
+
     // Player movement
     match ctx.key {
-        None => { return RunState::Paused } // Nothing happened
+        None => { return RunState::AwaitingInput } // Nothing happened
         Some(key) => match key {
             VirtualKeyCode::Left |
             VirtualKeyCode::Numpad4 |
@@ -71,9 +346,35 @@ pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
             VirtualKeyCode::Numpad1 |
             VirtualKeyCode::B => try_move_player(-1, 1, &mut gs.ecs),
 
-            _ => { return RunState::Paused }
+            // Skip a turn
+            VirtualKeyCode::Numpad5 |
+            VirtualKeyCode::Space => {}
+
+            VirtualKeyCode::Z => return start_auto_command(gs, AutoCommand::Rest { turns: 0 }),
+            VirtualKeyCode::X => return start_auto_command(gs, AutoCommand::Explore),
+
+            VirtualKeyCode::Escape => return RunState::SaveGame,
+
+            VirtualKeyCode::G => return get_item(&mut gs.ecs),
+            VirtualKeyCode::Period => return try_next_level(&mut gs.ecs),
+            VirtualKeyCode::I => return RunState::ShowInventory,
+            VirtualKeyCode::D => return RunState::ShowDropItem,
+            VirtualKeyCode::R => return RunState::ShowRemoveItem,
+
+            // Log scrollback doesn't cost a turn
+            VirtualKeyCode::PageUp => {
+                gs.ecs.fetch_mut::<GameLog>().scroll_back(1);
+                return RunState::AwaitingInput
+            }
+
+            VirtualKeyCode::PageDown => {
+                gs.ecs.fetch_mut::<GameLog>().scroll_forward(1);
+                return RunState::AwaitingInput
+            }
+
+            _ => { return RunState::AwaitingInput }
         },
     }
 
-    RunState::Running
+    RunState::PlayerTurn
 }
\ No newline at end of file
@@ -0,0 +1,94 @@
+use rltk::RandomNumberGenerator;
+
+struct RandomEntry {
+    name: String,
+    weight: i32,
+}
+
+/// Picks names with odds proportional to their weights. Entries with no weight are dropped,
+/// so a table can be built from everything and let the weights decide what is possible.
+#[derive(Default)]
+pub struct RandomTable {
+    entries: Vec<RandomEntry>,
+    total_weight: i32,
+}
+
+impl RandomTable {
+    pub fn new() -> Self {
+        RandomTable::default()
+    }
+
+    pub fn add<S: ToString>(mut self, name: S, weight: i32) -> Self {
+        if weight > 0 {
+            self.total_weight += weight;
+            self.entries.push(RandomEntry { name: name.to_string(), weight });
+        }
+        self
+    }
+
+    pub fn roll(&self, rng: &mut RandomNumberGenerator) -> Option<&str> {
+        if self.total_weight == 0 {
+            return None;
+        }
+
+        let mut roll = rng.roll_dice(1, self.total_weight) - 1;
+        for entry in self.entries.iter() {
+            if roll < entry.weight {
+                return Some(&entry.name);
+            }
+            roll -= entry.weight;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const ROLLS: usize = 10_000;
+
+    fn tally(table: &RandomTable, seed: u64) -> HashMap<String, usize> {
+        let mut rng = RandomNumberGenerator::seeded(seed);
+        let mut counts = HashMap::new();
+        for _i in 0..ROLLS {
+            let name = table.roll(&mut rng).expect("table has entries").to_string();
+            *counts.entry(name).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn rolls_follow_the_weights() {
+        let weights = [("Goblin", 10), ("Orc", 3), ("Health Potion", 7), ("Longsword", 1)];
+        let table = weights.iter().fold(RandomTable::new(), |table, (name, weight)| table.add(name, *weight));
+        let total: i32 = weights.iter().map(|(_name, weight)| weight).sum();
+
+        for seed in [1, 42, 1234] {
+            let counts = tally(&table, seed);
+            for (name, weight) in weights.iter() {
+                let expected = *weight as f64 / total as f64;
+                let observed = *counts.get(*name).unwrap_or(&0) as f64 / ROLLS as f64;
+                assert!((observed - expected).abs() < 0.02,
+                    "seed {}: {} came up {:.3} of the time, expected {:.3}", seed, name, observed, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_and_negative_weights_never_come_up() {
+        let table = RandomTable::new().add("Goblin", 5).add("Nothing", 0).add("Less than nothing", -3).add("Orc", 1);
+        let counts = tally(&table, 7);
+        assert!(!counts.contains_key("Nothing"));
+        assert!(!counts.contains_key("Less than nothing"));
+        assert_eq!(counts.values().sum::<usize>(), ROLLS);
+    }
+
+    #[test]
+    fn an_empty_table_rolls_nothing() {
+        let mut rng = RandomNumberGenerator::seeded(1);
+        assert_eq!(RandomTable::new().roll(&mut rng), None);
+        assert_eq!(RandomTable::new().add("Nothing", 0).roll(&mut rng), None);
+    }
+}
@@ -0,0 +1,162 @@
+use super::{RandomTable, Renderable};
+use rltk::{DiceType, RGB};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+
+mod raw_structs;
+pub use raw_structs::*;
+
+/// Monster and item definitions, read at startup so content can change without a rebuild.
+pub const RAWS_PATH: &str = "./raws/spawns.json";
+
+#[derive(Debug)]
+pub enum RawError {
+    Io { file: String, source: std::io::Error },
+    /// The file isn't valid JSON, or doesn't match the expected shape.
+    Schema { file: String, field: String, message: String },
+    /// The shape is fine but a value makes no sense, such as a two-letter glyph.
+    Invalid { file: String, field: String, message: String },
+}
+
+impl fmt::Display for RawError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RawError::Io { file, source } => write!(f, "Could not read {}: {}", file, source),
+            RawError::Schema { file, field, message } | RawError::Invalid { file, field, message } => {
+                write!(f, "{}: {}: {}", file, field, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RawError {}
+
+/// The loaded definitions, kept as an ECS resource for the spawner to build from.
+pub struct RawMaster {
+    raws: Raws,
+}
+
+impl RawMaster {
+    pub fn monster(&self, name: &str) -> Option<&RawMonster> {
+        self.raws.monsters.iter().find(|monster| monster.name == name)
+    }
+
+    pub fn item(&self, name: &str) -> Option<&RawItem> {
+        self.raws.items.iter().find(|item| item.name == name)
+    }
+
+    /// Everything that can spawn at `depth`, monsters and items together, weighted for that depth.
+    pub fn spawn_table(&self, depth: i32) -> RandomTable {
+        let monsters = self.raws.monsters.iter().map(|monster| (&monster.name, &monster.spawn));
+        let items = self.raws.items.iter().map(|item| (&item.name, &item.spawn));
+        monsters.chain(items).fold(RandomTable::new(), |table, (name, spawn)| table.add(name, spawn.weight_at(depth)))
+    }
+}
+
+pub fn load_raws(path: &str) -> Result<RawMaster, RawError> {
+    let text = fs::read_to_string(path).map_err(|source| RawError::Io { file: path.to_string(), source })?;
+    let raws = parse_raws(path, &text)?;
+    Ok(RawMaster { raws })
+}
+
+/// `file` is only used to label errors, so the same checks can run on text from anywhere.
+pub fn parse_raws(file: &str, text: &str) -> Result<Raws, RawError> {
+    let mut deserializer = serde_json::Deserializer::from_str(text);
+    let raws: Raws = serde_path_to_error::deserialize(&mut deserializer).map_err(|e| RawError::Schema {
+        file: file.to_string(),
+        field: e.path().to_string(),
+        message: e.inner().to_string(),
+    })?;
+
+    validate(&raws).map_err(|(field, message)| RawError::Invalid { file: file.to_string(), field, message })?;
+    Ok(raws)
+}
+
+/// Turns a raw glyph and colours into a component, or says which part is wrong.
+pub fn parse_renderable(raw: &RawRenderable) -> Result<Renderable, (&'static str, String)> {
+    let mut chars = raw.glyph.chars();
+    let glyph = match (chars.next(), chars.next()) {
+        (Some(glyph), None) => rltk::to_cp437(glyph),
+        _ => return Err(("glyph", format!("expected a single character, found \"{}\"", raw.glyph))),
+    };
+    let colour = |field: &'static str, code: &str| {
+        RGB::from_hex(code).map_err(|_| (field, format!("expected a colour like \"#FF0000\", found \"{}\"", code)))
+    };
+
+    Ok(Renderable { glyph, fg: colour("fg", &raw.fg)?, bg: colour("bg", &raw.bg)?, render_order: raw.order })
+}
+
+/// Reads dice like "2d6+1", rejecting any that could never roll.
+pub fn parse_dice(text: &str) -> Result<DiceType, String> {
+    match rltk::parse_dice_string(text) {
+        Ok(dice) if dice.n_dice > 0 && dice.die_type > 0 => Ok(dice),
+        _ => Err(format!("expected dice like \"1d6\" or \"2d4+1\", found \"{}\"", text)),
+    }
+}
+
+fn validate(raws: &Raws) -> Result<(), (String, String)> {
+    let mut names = HashSet::new();
+    let mut check_name = |field: String, name: &str| {
+        if name.is_empty() {
+            return Err((field, "names can't be empty".to_string()));
+        }
+        if !names.insert(name.to_string()) {
+            return Err((field, format!("\"{}\" is defined more than once", name)));
+        }
+        Ok(())
+    };
+    let check_renderable = |field: String, raw: &RawRenderable| {
+        parse_renderable(raw).map(|_| ()).map_err(|(part, message)| (format!("{}.{}", field, part), message))
+    };
+    let check_at_least = |field: String, value: i32, min: i32| {
+        if value < min {
+            return Err((field, format!("must be at least {}, found {}", min, value)));
+        }
+        Ok(())
+    };
+
+    let check_spawn = |field: String, spawn: &RawSpawn| -> Result<(), (String, String)> {
+        check_at_least(format!("{}.weight", field), spawn.weight, 0)?;
+        check_at_least(format!("{}.min_depth", field), spawn.min_depth, 1)?;
+        if let Some(max_depth) = spawn.max_depth {
+            check_at_least(format!("{}.max_depth", field), max_depth, spawn.min_depth)?;
+        }
+        Ok(())
+    };
+
+    for (i, monster) in raws.monsters.iter().enumerate() {
+        let field = format!("monsters[{}]", i);
+        check_name(format!("{}.name", field), &monster.name)?;
+        check_renderable(format!("{}.renderable", field), &monster.renderable)?;
+        check_at_least(format!("{}.vision_range", field), monster.vision_range, 1)?;
+        check_at_least(format!("{}.stats.max_hp", field), monster.stats.max_hp, 1)?;
+        check_at_least(format!("{}.xp", field), monster.xp, 0)?;
+        parse_dice(&monster.attack).map_err(|message| (format!("{}.attack", field), message))?;
+        check_spawn(format!("{}.spawn", field), &monster.spawn)?;
+    }
+
+    for (i, item) in raws.items.iter().enumerate() {
+        let field = format!("items[{}]", i);
+        check_name(format!("{}.name", field), &item.name)?;
+        check_renderable(format!("{}.renderable", field), &item.renderable)?;
+        check_spawn(format!("{}.spawn", field), &item.spawn)?;
+        if let Some(range) = item.range {
+            check_at_least(format!("{}.range", field), range, 1)?;
+        }
+        if let Some(dice) = item.equippable.as_ref().and_then(|equippable| equippable.dice.as_ref()) {
+            parse_dice(dice).map_err(|message| (format!("{}.equippable.dice", field), message))?;
+        }
+        if item.equippable.is_some() && item.consumable {
+            return Err((format!("{}.consumable", field), "equipment can't also be used up".to_string()));
+        }
+        if item.food && !item.consumable {
+            return Err((format!("{}.food", field), "food has to be consumable".to_string()));
+        }
+        if item.area_of_effect.is_some() && item.range.is_none() {
+            return Err((format!("{}.area_of_effect", field), "needs a range to be aimed with".to_string()));
+        }
+    }
+
+    Ok(())
+}
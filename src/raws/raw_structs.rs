@@ -0,0 +1,172 @@
+use super::super::EquipmentSlot;
+use serde::Deserialize;
+
+/// The whole of a raw data file. Unknown fields are rejected everywhere so a typo in a key
+/// is reported instead of silently falling back to a default.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Raws {
+    #[serde(default)]
+    pub monsters: Vec<RawMonster>,
+    #[serde(default)]
+    pub items: Vec<RawItem>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RawRenderable {
+    /// A single character, drawn from code page 437.
+    pub glyph: String,
+    /// Colours are HTML style, e.g. "#FF0000".
+    pub fg: String,
+    #[serde(default = "default_bg")]
+    pub bg: String,
+    pub order: i32,
+}
+
+fn default_bg() -> String {
+    "#000000".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RawMonster {
+    pub name: String,
+    pub renderable: RawRenderable,
+    pub vision_range: i32,
+    pub stats: RawStats,
+    #[serde(default)]
+    pub attributes: RawAttributes,
+    #[serde(default)]
+    pub skills: RawSkills,
+    /// Dice for its natural attack, e.g. "1d6".
+    pub attack: String,
+    /// Awarded to whoever kills it.
+    pub xp: i32,
+    pub spawn: RawSpawn,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RawStats {
+    pub max_hp: i32,
+    pub defence: i32,
+    pub power: i32,
+}
+
+/// Any attribute left out is an average 10.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct RawAttributes {
+    pub might: i32,
+    pub fitness: i32,
+    pub quickness: i32,
+    pub intelligence: i32,
+}
+
+impl Default for RawAttributes {
+    fn default() -> Self {
+        RawAttributes { might: 10, fitness: 10, quickness: 10, intelligence: 10 }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct RawSkills {
+    pub melee: i32,
+    pub defence: i32,
+    pub magic: i32,
+}
+
+/// Every effect is optional; an item gets the components for whichever are present.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RawItem {
+    pub name: String,
+    pub renderable: RawRenderable,
+    #[serde(default)]
+    pub consumable: bool,
+    pub healing: Option<i32>,
+    pub range: Option<i32>,
+    pub damage: Option<i32>,
+    pub area_of_effect: Option<i32>,
+    pub confusion: Option<i32>,
+    /// Eating it leaves the eater well fed.
+    #[serde(default)]
+    pub food: bool,
+    pub equippable: Option<RawEquippable>,
+    pub spawn: RawSpawn,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RawEquippable {
+    pub slot: EquipmentSlot,
+    #[serde(default)]
+    pub power_bonus: i32,
+    #[serde(default)]
+    pub defence_bonus: i32,
+    /// Damage dice for a weapon, e.g. "1d8". Without them the wielder keeps its own attack.
+    pub dice: Option<String>,
+}
+
+/// How often something turns up. The weight grows by `per_depth` for each level below the
+/// first, and is zero outside `min_depth..=max_depth`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RawSpawn {
+    pub weight: i32,
+    #[serde(default)]
+    pub per_depth: i32,
+    #[serde(default = "default_min_depth")]
+    pub min_depth: i32,
+    pub max_depth: Option<i32>,
+}
+
+fn default_min_depth() -> i32 {
+    1
+}
+
+impl RawSpawn {
+    pub fn weight_at(&self, depth: i32) -> i32 {
+        if depth < self.min_depth || self.max_depth.map(|max| depth > max).unwrap_or(false) {
+            return 0;
+        }
+        i32::max(0, self.weight + self.per_depth * (depth - 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawn(weight: i32, per_depth: i32, min_depth: i32, max_depth: Option<i32>) -> RawSpawn {
+        RawSpawn { weight, per_depth, min_depth, max_depth }
+    }
+
+    #[test]
+    fn weight_grows_by_per_depth_below_the_first_level() {
+        let rule = spawn(2, 3, 1, None);
+        assert_eq!(rule.weight_at(1), 2);
+        assert_eq!(rule.weight_at(2), 5);
+        assert_eq!(rule.weight_at(5), 14);
+    }
+
+    #[test]
+    fn weight_is_zero_outside_the_depth_range() {
+        let rule = spawn(4, 0, 3, Some(5));
+        assert_eq!(rule.weight_at(1), 0);
+        assert_eq!(rule.weight_at(2), 0);
+        assert_eq!(rule.weight_at(3), 4);
+        assert_eq!(rule.weight_at(5), 4);
+        assert_eq!(rule.weight_at(6), 0);
+    }
+
+    #[test]
+    fn shrinking_weights_stop_at_zero() {
+        let rule = spawn(5, -2, 1, None);
+        assert_eq!(rule.weight_at(3), 1);
+        assert_eq!(rule.weight_at(4), 0);
+        assert_eq!(rule.weight_at(10), 0);
+    }
+}
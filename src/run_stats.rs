@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+/// Running totals for the current run, reported on the death screen.
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct RunStats {
+    pub seed: u64,
+    pub turns: i32,
+    pub kills: i32,
+    pub last_attacker: Option<String>,
+    pub cause_of_death: Option<String>,
+}
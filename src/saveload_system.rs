@@ -0,0 +1,200 @@
+use specs::prelude::*;
+use specs::saveload::{DeserializeComponents, MarkedBuilder, SerializeComponents, SimpleMarker, SimpleMarkerAllocator};
+use specs::error::NoError;
+use rltk::RandomNumberGenerator;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File};
+use std::path::Path;
+use super::components::*;
+use super::{GameLog, Map, MapIndexingSystem, RunStats};
+
+const SAVE_PATH: &str = "./savegame.json";
+/// Saves are written here first and only moved over `SAVE_PATH` once complete.
+const SAVE_TEMP_PATH: &str = "./savegame.json.tmp";
+const SAVE_MAGIC: &str = "rustly-save";
+
+/// Bump whenever a component or resource changes shape, so older saves are refused cleanly.
+pub const SAVE_VERSION: u32 = 7;
+
+#[derive(Serialize, Deserialize)]
+struct SaveHeader {
+    magic: String,
+    version: u32,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Format(serde_json::Error),
+    NotASave,
+    UnsupportedVersion { found: u32 },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "Could not access the save file: {}", e),
+            SaveError::Format(e) => write!(f, "The save file is damaged: {}", e),
+            SaveError::NotASave => write!(f, "{} is not a Rustly save file", SAVE_PATH),
+            SaveError::UnsupportedVersion { found } => {
+                write!(f, "The save file is version {}, but this build reads version {}", found, SAVE_VERSION)
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for SaveError {
+    fn from(e: std::io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> Self {
+        SaveError::Format(e)
+    }
+}
+
+macro_rules! serialize_individually {
+    ($ecs:expr, $ser:expr, $data:expr, $( $type:ty),*) => {
+        $(
+        SerializeComponents::<NoError, SimpleMarker<SerializeMe>>::serialize(
+            &( $ecs.read_storage::<$type>(), ),
+            &$data.0,
+            &$data.1,
+            &mut $ser,
+        )?;
+        )*
+    };
+}
+
+macro_rules! deserialize_individually {
+    ($ecs:expr, $de:expr, $data:expr, $( $type:ty),*) => {
+        $(
+        DeserializeComponents::<NoError, _>::deserialize(
+            &mut ( &mut $ecs.write_storage::<$type>(), ),
+            &$data.0,
+            &mut $data.1,
+            &mut $data.2,
+            &mut $de,
+        )?;
+        )*
+    };
+}
+
+pub fn does_save_exist() -> bool {
+    Path::new(SAVE_PATH).exists()
+}
+
+/// Best effort: a save that can't be removed is only a stale file, not worth crashing over.
+pub fn delete_save() {
+    if does_save_exist() {
+        if let Err(e) = fs::remove_file(SAVE_PATH) {
+            eprintln!("Could not delete {}: {}", SAVE_PATH, e);
+        }
+    }
+}
+
+pub fn save_game(ecs: &mut World) -> Result<(), SaveError> {
+    // Resources aren't components, so they travel on a helper entity for the duration of the save
+    let helper = SerializationHelper {
+        map: (*ecs.fetch::<Map>()).clone(),
+        run_stats: (*ecs.fetch::<RunStats>()).clone(),
+        log: (*ecs.fetch::<GameLog>()).clone(),
+        rng: (*ecs.fetch::<RandomNumberGenerator>()).clone(),
+    };
+    let save_helper = ecs
+        .create_entity()
+        .with(helper)
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+
+    // A save that fails partway must not cost the player the last good one
+    let result = write_save(ecs).and_then(|()| fs::rename(SAVE_TEMP_PATH, SAVE_PATH).map_err(SaveError::from));
+    if result.is_err() {
+        let _ = fs::remove_file(SAVE_TEMP_PATH);
+    }
+
+    ecs.delete_entity(save_helper).expect("Unable to delete save helper");
+    result
+}
+
+fn write_save(ecs: &World) -> Result<(), SaveError> {
+    let data = ( ecs.entities(), ecs.read_storage::<SimpleMarker<SerializeMe>>() );
+
+    let writer = File::create(SAVE_TEMP_PATH)?;
+    let mut serializer = serde_json::Serializer::new(writer);
+
+    SaveHeader { magic: SAVE_MAGIC.to_string(), version: SAVE_VERSION }.serialize(&mut serializer)?;
+    // SufferDamage is left out: it only lives for the turn that creates it
+    serialize_individually!(ecs, serializer, data, Position, Renderable, Player, Viewshed, Monster, Name, BlocksTile,
+        CombatStats, WantsToMelee, ReceiveHealing, Item, InBackpack, WantsToPickupItem, WantsToDropItem,
+        Consumable, ProvidesHealing, WantsToUseItem, Ranged, InflictsDamage, AreaOfEffect, Confusion,
+        Equippable, Equipped, MeleePowerBonus, DefenceBonus, WantsToRemoveItem, Experience, ExperienceValue,
+        Attributes, Skills, DamageDice, HungerClock, ProvidesFood, SerializationHelper);
+
+    serializer.into_inner().sync_all()?;
+    Ok(())
+}
+
+/// Replaces the current world with the saved one. The header is checked before anything is
+/// touched, so a save from another version leaves the current world intact.
+pub fn load_game(ecs: &mut World) -> Result<(), SaveError> {
+    let data = fs::read_to_string(SAVE_PATH)?;
+    let mut de = serde_json::Deserializer::from_str(&data);
+
+    let header = SaveHeader::deserialize(&mut de).map_err(|_| SaveError::NotASave)?;
+    if header.magic != SAVE_MAGIC {
+        return Err(SaveError::NotASave);
+    }
+    if header.version != SAVE_VERSION {
+        return Err(SaveError::UnsupportedVersion { found: header.version });
+    }
+
+    ecs.delete_all();
+
+    {
+        let mut d = (
+            &mut ecs.entities(),
+            &mut ecs.write_storage::<SimpleMarker<SerializeMe>>(),
+            &mut ecs.write_resource::<SimpleMarkerAllocator<SerializeMe>>(),
+        );
+        deserialize_individually!(ecs, de, d, Position, Renderable, Player, Viewshed, Monster, Name, BlocksTile,
+            CombatStats, WantsToMelee, ReceiveHealing, Item, InBackpack, WantsToPickupItem, WantsToDropItem,
+            Consumable, ProvidesHealing, WantsToUseItem, Ranged, InflictsDamage, AreaOfEffect, Confusion,
+            Equippable, Equipped, MeleePowerBonus, DefenceBonus, WantsToRemoveItem, Experience, ExperienceValue,
+            Attributes, Skills, DamageDice, HungerClock, ProvidesFood, SerializationHelper);
+    }
+    ecs.maintain();
+
+    let (helper_entity, helper) = {
+        let entities = ecs.entities();
+        let helpers = ecs.read_storage::<SerializationHelper>();
+        (&entities, &helpers).join().map(|(e, h)| (e, h.clone())).next().ok_or(SaveError::NotASave)?
+    };
+    ecs.delete_entity(helper_entity).expect("Unable to delete helper");
+
+    let player = {
+        let entities = ecs.entities();
+        let players = ecs.read_storage::<Player>();
+        let positions = ecs.read_storage::<Position>();
+        (&entities, &players, &positions).join().map(|(e, _p, pos)| (e, rltk::Point::new(pos.x, pos.y))).next()
+    };
+    let (player_entity, player_pos) = player.ok_or(SaveError::NotASave)?;
+    ecs.insert(player_entity);
+    ecs.insert(player_pos);
+
+    let mut map = helper.map;
+    map.blocked = vec![false; map.tiles.len()];
+    map.tile_content = vec![Vec::new(); map.tiles.len()];
+    ecs.insert(map);
+    ecs.insert(helper.run_stats);
+    ecs.insert(helper.log);
+    ecs.insert(helper.rng);
+
+    let mut map_indexer = MapIndexingSystem{};
+    map_indexer.run_now(ecs);
+
+    Ok(())
+}
@@ -0,0 +1,23 @@
+use super::map_builders::MapBuilderKind;
+
+/// Player preferences chosen from the main menu. They outlive individual runs.
+pub struct Settings {
+    pub show_tooltips: bool,
+    /// Seed for the next new game; `None` picks a fresh one each time.
+    pub seed: Option<u64>,
+    /// Generator used for every level; `None` picks one at random per level.
+    pub map_builder: Option<MapBuilderKind>,
+    /// Replays each level's generation before play starts. Meant for tuning the builders.
+    pub show_mapgen: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            show_tooltips: true,
+            seed: None,
+            map_builder: None,
+            show_mapgen: false,
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use rltk::{RandomNumberGenerator, RGB};
+use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
+use super::raws::{parse_dice, parse_renderable, RawItem, RawMaster, RawMonster};
+use super::{AreaOfEffect, Attributes, BlocksTile, CombatStats, Confusion, Consumable, DamageDice, DefenceBonus, Equippable,
+    Experience, ExperienceValue, HungerClock, InflictsDamage, Item, MeleePowerBonus, Monster, Name, Player, Position, ProvidesFood,
+    ProvidesHealing, RandomTable, Ranged, Renderable, SerializeMe, Skills, Viewshed, MAPWIDTH};
+
+pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position {
+            x: player_x,
+            y: player_y,
+        })
+        .with(Renderable {
+            glyph: rltk::to_cp437('@'),
+            fg: RGB::named(rltk::YELLOW),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 0,
+        })
+        .with(Player {})
+        .with(Viewshed {
+            visible_tiles: Vec::new(),
+            range: 8,
+            dirty: true,
+        })
+        .with(Name{ name: "Player".to_string() })
+        .with(CombatStats{
+                max_hp: 30,
+                current_hp: 30,
+                defence: 0,
+                power: 0
+            })
+        .with(Attributes { might: 12, fitness: 12, quickness: 12, intelligence: 12 })
+        .with(Skills { melee: 1, defence: 1, magic: 1 })
+        .with(DamageDice { dice: rltk::DiceType { n_dice: 1, die_type: 4, bonus: 0 } })
+        .with(Experience { level: 1, xp: 0, unspent_levels: 0 })
+        .with(HungerClock::well_fed())
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+/// Regions hold up to this many things at depth 1, and one more for each level below that.
+const MAX_SPAWNS: i32 = 4;
+
+/// Rolls how many things a region holds, then places each pick from `table` on its own free
+/// floor tile. `spawned` keeps monster numbers unique across the level.
+pub fn spawn_region(ecs: &mut World, rng: &mut RandomNumberGenerator, table: &RandomTable, region: &[usize], depth: i32,
+    spawned: &mut usize) {
+    let num_spawns = rng.roll_dice(1, MAX_SPAWNS + 3) + (depth - 1) - 3;
+
+    let mut free_tiles = region.to_vec();
+    let mut spawn_points = Vec::new();
+    for _i in 0..num_spawns {
+        if free_tiles.is_empty() {
+            break;
+        }
+        let idx = free_tiles.swap_remove(rng.range(0, free_tiles.len() as i32) as usize);
+        if let Some(name) = table.roll(rng) {
+            spawn_points.push((idx, name.to_string()));
+        }
+    }
+
+    let width = MAPWIDTH as i32;
+    for (idx, name) in spawn_points {
+        spawn_named(ecs, &name, idx as i32 % width, idx as i32 / width, depth, spawned);
+    }
+}
+
+/// Builds whichever raw definition goes by `name`.
+pub fn spawn_named(ecs: &mut World, name: &str, x: i32, y: i32, depth: i32, spawned: &mut usize) {
+    let (monster, item) = {
+        let raws = ecs.fetch::<RawMaster>();
+        (raws.monster(name).cloned(), raws.item(name).cloned())
+    };
+
+    if let Some(monster) = monster {
+        *spawned += 1;
+        spawn_monster(ecs, &monster, x, y, *spawned, depth);
+    } else if let Some(item) = item {
+        spawn_item(ecs, &item, x, y);
+    }
+}
+
+/// Monsters get tougher the deeper they are found.
+pub fn spawn_monster(ecs: &mut World, monster: &RawMonster, x: i32, y: i32, number: usize, depth: i32) -> Entity {
+    let renderable = parse_renderable(&monster.renderable).expect("Raws are validated when loaded");
+    let max_hp = monster.stats.max_hp + (depth - 1) * 2;
+    let attack = parse_dice(&monster.attack).expect("Raws are validated when loaded");
+    let attributes = &monster.attributes;
+    let skills = &monster.skills;
+
+    ecs.create_entity()
+        .with(Monster {})
+        .with(Name{ name: format!("{} #{}", monster.name, number) })
+        .with(Position { x, y })
+        .with(renderable)
+        .with(Viewshed {
+            range: monster.vision_range,
+            visible_tiles: Vec::new(),
+            dirty: true,
+        })
+        .with(BlocksTile{})
+        .with(ExperienceValue { xp: monster.xp })
+        .with(CombatStats{
+            max_hp,
+            current_hp: max_hp,
+            defence: monster.stats.defence + (depth - 1) / 3,
+            power: monster.stats.power + (depth - 1) / 2
+        })
+        .with(Attributes {
+            might: attributes.might,
+            fitness: attributes.fitness,
+            quickness: attributes.quickness,
+            intelligence: attributes.intelligence
+        })
+        .with(Skills { melee: skills.melee, defence: skills.defence, magic: skills.magic })
+        .with(DamageDice { dice: attack })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+/// Builds an item lying on the floor, with a component for each effect its definition lists.
+pub fn spawn_item(ecs: &mut World, item: &RawItem, x: i32, y: i32) -> Entity {
+    let renderable = parse_renderable(&item.renderable).expect("Raws are validated when loaded");
+
+    let mut builder = ecs.create_entity()
+        .with(Position { x, y })
+        .with(renderable)
+        .with(Name{ name: item.name.clone() })
+        .with(Item {});
+
+    if item.consumable {
+        builder = builder.with(Consumable {});
+    }
+    if let Some(amount) = item.healing {
+        builder = builder.with(ProvidesHealing { amount });
+    }
+    if let Some(range) = item.range {
+        builder = builder.with(Ranged { range });
+    }
+    if let Some(damage) = item.damage {
+        builder = builder.with(InflictsDamage { damage });
+    }
+    if let Some(radius) = item.area_of_effect {
+        builder = builder.with(AreaOfEffect { radius });
+    }
+    if let Some(turns) = item.confusion {
+        builder = builder.with(Confusion { turns });
+    }
+    if item.food {
+        builder = builder.with(ProvidesFood {});
+    }
+    if let Some(equippable) = &item.equippable {
+        builder = builder.with(Equippable { slot: equippable.slot });
+        if equippable.power_bonus != 0 {
+            builder = builder.with(MeleePowerBonus { power: equippable.power_bonus });
+        }
+        if equippable.defence_bonus != 0 {
+            builder = builder.with(DefenceBonus { defence: equippable.defence_bonus });
+        }
+        if let Some(dice) = &equippable.dice {
+            builder = builder.with(DamageDice { dice: parse_dice(dice).expect("Raws are validated when loaded") });
+        }
+    }
+
+    builder.marked::<SimpleMarker<SerializeMe>>().build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::raws::{load_raws, RAWS_PATH};
+    use specs::saveload::SimpleMarkerAllocator;
+    use std::collections::HashSet;
+
+    fn world_with_raws() -> World {
+        let mut ecs = World::new();
+        ecs.register::<Position>();
+        ecs.register::<Renderable>();
+        ecs.register::<Name>();
+        ecs.register::<Monster>();
+        ecs.register::<Viewshed>();
+        ecs.register::<BlocksTile>();
+        ecs.register::<ExperienceValue>();
+        ecs.register::<CombatStats>();
+        ecs.register::<Attributes>();
+        ecs.register::<Skills>();
+        ecs.register::<DamageDice>();
+        ecs.register::<Item>();
+        ecs.register::<Consumable>();
+        ecs.register::<ProvidesHealing>();
+        ecs.register::<ProvidesFood>();
+        ecs.register::<Ranged>();
+        ecs.register::<InflictsDamage>();
+        ecs.register::<AreaOfEffect>();
+        ecs.register::<Confusion>();
+        ecs.register::<Equippable>();
+        ecs.register::<MeleePowerBonus>();
+        ecs.register::<DefenceBonus>();
+        ecs.register::<SimpleMarker<SerializeMe>>();
+        ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        ecs.insert(load_raws(RAWS_PATH).expect("the shipped raws load"));
+        ecs
+    }
+
+    #[test]
+    fn spawns_never_share_a_tile_or_leave_the_region() {
+        let mut ecs = world_with_raws();
+        let region: Vec<usize> = (0..6).map(|x| 5 * MAPWIDTH + 10 + x).collect();
+        let mut most_spawned = 0;
+
+        for seed in 0..200 {
+            let mut rng = RandomNumberGenerator::seeded(seed);
+            // Deep enough that the roll often asks for more spawns than the region has tiles
+            let depth = 1 + seed as i32 % 10;
+            let table = ecs.fetch::<RawMaster>().spawn_table(depth);
+            let mut spawned = 0;
+            spawn_region(&mut ecs, &mut rng, &table, &region, depth, &mut spawned);
+
+            let mut tiles = HashSet::new();
+            for pos in ecs.read_storage::<Position>().join() {
+                let idx = pos.y as usize * MAPWIDTH + pos.x as usize;
+                assert!(region.contains(&idx), "seed {}: spawned outside the region at {}", seed, idx);
+                assert!(tiles.insert(idx), "seed {}: two spawns on tile {}", seed, idx);
+            }
+            most_spawned = usize::max(most_spawned, tiles.len());
+
+            ecs.delete_all();
+            ecs.maintain();
+        }
+        assert_eq!(most_spawned, region.len(), "no roll ever filled the region");
+    }
+}
@@ -0,0 +1,36 @@
+use rltk::RGB;
+use specs::prelude::*;
+use super::{Confusion, GameLog, Item, LogCategory, Name, RunStats};
+
+/// Counts down timed status effects once per turn and removes those that have worn off.
+pub struct StatusEffectSystem {}
+
+impl<'a> System<'a> for StatusEffectSystem {
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, RunStats>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, Confusion>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, Item>
+                      );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, run_stats, mut log, mut confused, names, items) = data;
+
+        // Items carry a Confusion to describe what they inflict; that one mustn't wear off
+        let mut recovered: Vec<Entity> = Vec::new();
+        for (entity, confusion, _not_item) in (&entities, &mut confused, !&items).join() {
+            confusion.turns -= 1;
+            if confusion.turns < 1 {
+                recovered.push(entity);
+            }
+        }
+
+        for entity in recovered {
+            confused.remove(entity);
+            if let Some(name) = names.get(entity) {
+                log.push(run_stats.turns, LogCategory::Combat, RGB::named(rltk::GREY), format!("{} is no longer confused.", name.name));
+            }
+        }
+    }
+}